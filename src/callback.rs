@@ -0,0 +1,75 @@
+use crate::ptr::SharedPtr;
+use crate::WeakSelf;
+
+/// Callbacks holding a Weak<T> pointer to the content, for registering with other components
+/// without keeping the content alive.
+///
+/// The callbacks take a single argument; use a tuple to pass several values.
+impl<T: ?Sized, P: SharedPtr<T>> WeakSelf<T, P> {
+    /// Create a callback which calls the closure with an Arc<T> to the content, or returns None
+    /// once the content has been dropped.
+    ///
+    /// Panics if the WeakSelf<T> is not yet initialized.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// pub struct Counter {
+    ///     weak_self: WeakSelf<Counter>,
+    ///     step: usize,
+    /// }
+    ///
+    /// let counter = WeakSelf::cyclic(|weak_self| Counter { weak_self, step: 2 });
+    /// let next = counter.weak_self.callback(|this: Arc<Counter>, value: usize| value + this.step);
+    /// assert_eq!(next(1), Some(3));
+    ///
+    /// drop(counter);
+    /// assert_eq!(next(1), None);
+    ///```
+    pub fn callback<A, R, F>(&self, f: F) -> impl Fn(A) -> Option<R>
+        where F: Fn(P::Strong, A) -> R
+    {
+        let weak = self.get();
+        move |args| P::upgrade(&weak).map(|this| f(this, args))
+    }
+
+    /// Like [`WeakSelf::callback`], but for closures that mutate their own state.
+    ///
+    /// Panics if the WeakSelf<T> is not yet initialized.
+    pub fn callback_mut<A, R, F>(&self, mut f: F) -> impl FnMut(A) -> Option<R>
+        where F: FnMut(P::Strong, A) -> R
+    {
+        let weak = self.get();
+        move |args| P::upgrade(&weak).map(|this| f(this, args))
+    }
+
+    /// Like [`WeakSelf::callback`], but returns a clone of default once the content has been dropped.
+    ///
+    /// Panics if the WeakSelf<T> is not yet initialized.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// pub struct Filter {
+    ///     weak_self: WeakSelf<Filter>,
+    ///     max: u32,
+    /// }
+    ///
+    /// let filter = WeakSelf::cyclic(|weak_self| Filter { weak_self, max: 10 });
+    /// let accept = filter.weak_self.callback_or(true, |this: Arc<Filter>, value: u32| value <= this.max);
+    /// assert!(!accept(11));
+    ///
+    /// drop(filter);
+    /// assert!(accept(11));
+    ///```
+    pub fn callback_or<A, R, F>(&self, default: R, f: F) -> impl Fn(A) -> R
+        where F: Fn(P::Strong, A) -> R,
+              R: Clone
+    {
+        let weak = self.get();
+        move |args| match P::upgrade(&weak) {
+            Some(this) => f(this, args),
+            None => default.clone(),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Event emitter whose subscribers are held through their [`WeakSelf`].
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::sync::lock;
+use crate::WeakSelf;
+
+struct Listener<E: ?Sized> {
+    active: AtomicBool,
+    /// calls the subscriber, or returns false if it has been dropped
+    call: Box<dyn Fn(&E) -> bool + Send + Sync>,
+}
+
+struct Listeners<E: ?Sized> {
+    next_id: u64,
+    entries: Vec<(u64, Arc<Listener<E>>)>,
+}
+
+///Emits events to subscribers without keeping them alive.
+///
+///Subscribers register through their WeakSelf<T> and get a [`Subscription`], which unsubscribes
+///when dropped. Subscribers which have been dropped are removed lazily while emitting. Clones of
+///an Emitter<E> share the same subscribers.
+///
+///## Re-entrancy
+///
+///No lock is held while subscribers are called, so a subscriber may subscribe, unsubscribe and
+///emit from within its callback:
+///
+///* Each emit delivers to the subscribers registered when it started. Subscribers added during an
+///  emit only receive later events.
+///* A subscriber which is unsubscribed during an emit is not called anymore, also not by the
+///  emit in progress.
+///* An emit from within a callback is delivered to all subscribers before the outer emit continues.
+///
+///```rust
+/// use weak_self::WeakSelf;
+/// use weak_self::emitter::Emitter;
+/// use std::sync::{Arc, Mutex};
+/// pub struct Logger {
+///     weak_self: WeakSelf<Logger>,
+///     lines: Mutex<Vec<String>>,
+/// }
+///
+/// let emitter: Emitter<str> = Emitter::new();
+/// let logger = WeakSelf::cyclic(|weak_self| Logger { weak_self, lines: Mutex::new(Vec::new()) });
+/// let subscription = emitter.subscribe(&logger.weak_self, |this: Arc<Logger>, line: &str| {
+///     this.lines.lock().unwrap().push(line.to_string());
+/// });
+///
+/// assert_eq!(emitter.emit("hello"), 1);
+/// assert_eq!(*logger.lines.lock().unwrap(), ["hello"]);
+///
+/// drop(subscription);
+/// assert_eq!(emitter.emit("world"), 0);
+///```
+pub struct Emitter<E: ?Sized> {
+    listeners: Arc<Mutex<Listeners<E>>>,
+}
+
+impl<E: ?Sized + 'static> Emitter<E> {
+    /// Constructs a new Emitter<E> without subscribers
+    pub fn new() -> Emitter<E> {
+        Emitter {
+            listeners: Arc::new(Mutex::new(Listeners {
+                next_id: 0,
+                entries: Vec::new(),
+            })),
+        }
+    }
+
+    /// Subscribe the content of weak_self, which receives events as long as it is alive and the
+    /// returned Subscription is not dropped.
+    ///
+    /// Panics if the WeakSelf<T> is not yet initialized.
+    #[must_use = "dropping the Subscription unsubscribes immediately"]
+    pub fn subscribe<T, F>(&self, weak_self: &WeakSelf<T>, f: F) -> Subscription
+        where T: ?Sized + Send + Sync + 'static,
+              F: Fn(Arc<T>, &E) + Send + Sync + 'static
+    {
+        let weak = weak_self.get();
+        let listener = Arc::new(Listener {
+            active: AtomicBool::new(true),
+            call: Box::new(move |event: &E| match weak.upgrade() {
+                Some(this) => {
+                    f(this, event);
+                    true
+                }
+                None => false,
+            }),
+        });
+        let mut listeners = lock(&self.listeners);
+        let id = listeners.next_id;
+        listeners.next_id += 1;
+        listeners.entries.push((id, listener.clone()));
+        Subscription {
+            unsubscribe: Some(Box::new(Unsubscribe {
+                listeners: Arc::downgrade(&self.listeners),
+                id,
+                listener,
+            })),
+        }
+    }
+
+    /// Deliver an event to all live subscribers, returning the number of subscribers called
+    pub fn emit(&self, event: &E) -> usize {
+        let snapshot: Vec<(u64, Arc<Listener<E>>)> = lock(&self.listeners).entries.clone();
+        let mut called = 0;
+        let mut dead = Vec::new();
+        for (id, listener) in snapshot {
+            if !listener.active.load(Ordering::Acquire) {
+                continue;
+            }
+            if (listener.call)(event) {
+                called += 1;
+            } else {
+                dead.push(id);
+            }
+        }
+        if !dead.is_empty() {
+            // dropped outside of the lock, as dropping a listener drops its closure
+            let removed: Vec<(u64, Arc<Listener<E>>)> = {
+                let mut listeners = lock(&self.listeners);
+                let (removed, kept) = std::mem::take(&mut listeners.entries)
+                    .into_iter()
+                    .partition(|(id, _)| dead.contains(id));
+                listeners.entries = kept;
+                removed
+            };
+            drop(removed);
+        }
+        called
+    }
+}
+
+impl<E: ?Sized> Clone for Emitter<E> {
+    fn clone(&self) -> Self {
+        Emitter {
+            listeners: self.listeners.clone(),
+        }
+    }
+}
+
+impl<E: ?Sized + 'static> Default for Emitter<E> {
+    fn default() -> Self {
+        Emitter::new()
+    }
+}
+
+impl<E: ?Sized> fmt::Debug for Emitter<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let subscribers = lock(&self.listeners).entries.len();
+        f.debug_struct("Emitter").field("subscribers", &subscribers).finish()
+    }
+}
+
+trait Cancel: Send + Sync {
+    fn cancel(&self);
+}
+
+struct Unsubscribe<E: ?Sized> {
+    listeners: Weak<Mutex<Listeners<E>>>,
+    id: u64,
+    listener: Arc<Listener<E>>,
+}
+
+impl<E: ?Sized> Cancel for Unsubscribe<E> {
+    fn cancel(&self) {
+        self.listener.active.store(false, Ordering::Release);
+        if let Some(listeners) = self.listeners.upgrade() {
+            lock(&listeners).entries.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+/// Handle of a subscription to an [`Emitter`], which unsubscribes when dropped
+#[must_use = "dropping the Subscription unsubscribes immediately"]
+pub struct Subscription {
+    unsubscribe: Option<Box<dyn Cancel>>,
+}
+
+impl Subscription {
+    /// Unsubscribe now, same as dropping the Subscription
+    pub fn unsubscribe(self) {}
+
+    /// Keep the subscription for as long as the subscriber and the emitter are alive
+    pub fn detach(mut self) {
+        self.unsubscribe = None;
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe.cancel();
+        }
+    }
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Subscription").field("active", &self.unsubscribe.is_some()).finish()
+    }
+}
@@ -0,0 +1,57 @@
+use core::error::Error;
+use core::fmt;
+
+/// Reason why a WeakSelf<T> could not be initialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InitError {
+    /// The WeakSelf<T> already holds a Weak<T> pointer
+    AlreadyInitialized,
+    /// Other strong references to the content exist; holds the number of those other references
+    StrongReferences(usize),
+    /// Weak references to the content exist; holds the number of those references
+    WeakReferences(usize),
+    /// The coercion passed to [`WeakSelf::try_init_coerced`](crate::WeakSelf::try_init_coerced)
+    /// returned a pointer to other content
+    CoercedToOtherContent,
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InitError::AlreadyInitialized => write!(f, "WeakSelf<T> is already initialized"),
+            InitError::StrongReferences(count) => write!(
+                f,
+                "Exclusive access to the content is required while initializing WeakSelf<T>, but {} other strong reference(s) exist",
+                count
+            ),
+            InitError::WeakReferences(count) => write!(
+                f,
+                "Exclusive access to the content is required while initializing WeakSelf<T>, but {} weak reference(s) exist",
+                count
+            ),
+            InitError::CoercedToOtherContent => write!(f, "coercion of WeakSelf<T> must keep pointing to the same content"),
+        }
+    }
+}
+
+impl Error for InitError {}
+
+/// Reason why a WeakSelf<T> could not provide an Arc<T> to its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WeakSelfError {
+    /// The WeakSelf<T> has not been initialized yet
+    NotInitialized,
+    /// The content has already been dropped
+    Expired,
+}
+
+impl fmt::Display for WeakSelfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WeakSelfError::NotInitialized => write!(f, "expected WeakSelf to be initialized"),
+            WeakSelfError::Expired => write!(f, "the content of WeakSelf has already been dropped"),
+        }
+    }
+}
+
+impl Error for WeakSelfError {}
@@ -0,0 +1,226 @@
+//! Futures tied to the lifetime of the content of a [`WeakSelf`].
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::ptr::SharedPtr;
+use crate::WeakSelf;
+
+#[cfg(feature = "std")]
+pub use crate::wait::Initialized;
+
+impl<T: ?Sized, P: SharedPtr<T>> WeakSelf<T, P> {
+    /// Create a future which polls the closure with an Arc<T> to the content, and resolves to
+    /// None once the content has been dropped.
+    ///
+    /// The Arc<T> is upgraded for each poll and released when the closure returns, so a task
+    /// running this future never keeps the content alive. The closure works like the `poll`
+    /// method of a future: it returns `Poll::Pending` after arranging for the waker in the
+    /// context to be woken, or `Poll::Ready` with the result. The future does not depend on any
+    /// particular async runtime.
+    ///
+    /// Panics if the WeakSelf<T> is not yet initialized. See [`WeakSelf::run_async`] for tasks
+    /// written with `async` and `.await`.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::task::{Context, Poll, Waker};
+    /// pub struct Worker {
+    ///     weak_self: WeakSelf<Worker>,
+    ///     jobs: AtomicUsize,
+    /// }
+    ///
+    /// let worker = WeakSelf::cyclic(|weak_self| Worker { weak_self, jobs: AtomicUsize::new(0) });
+    /// let mut task = pin!(worker.weak_self.run(|this: Arc<Worker>, cx: &mut Context| {
+    ///     this.jobs.fetch_add(1, Ordering::SeqCst);
+    ///     cx.waker().wake_by_ref();
+    ///     Poll::<()>::Pending
+    /// }));
+    ///
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// assert_eq!(task.as_mut().poll(&mut cx), Poll::Pending);
+    /// assert_eq!(worker.jobs.load(Ordering::SeqCst), 1);
+    ///
+    /// drop(worker);
+    /// assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(None));
+    ///```
+    pub fn run<R, F>(&self, f: F) -> Run<T, P, F>
+        where F: FnMut(P::Strong, &mut Context<'_>) -> Poll<R>
+    {
+        Run {
+            weak: self.get(),
+            f,
+        }
+    }
+
+    /// Create a future which runs an async task on the content, and resolves to None once the
+    /// content has been dropped.
+    ///
+    /// An `async move` block holding an Arc<T> would keep the content alive until it completes,
+    /// so the closure gets a [`Handle`] instead. The task upgrades the handle for each
+    /// synchronous step with `handle.with(|this| ...).await`, which releases the Arc<T> before
+    /// the task awaits anything else. Once the content has been dropped, `with` never completes,
+    /// and the future drops the task and resolves to None.
+    ///
+    /// Panics if the WeakSelf<T> is not yet initialized.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::task::{Context, Poll, Waker};
+    /// pub struct Worker {
+    ///     weak_self: WeakSelf<Worker>,
+    ///     jobs: AtomicUsize,
+    /// }
+    ///
+    /// /// stands in for a timer or channel of an async runtime
+    /// async fn tick() {
+    ///     let mut ready = false;
+    ///     std::future::poll_fn(|_| match std::mem::replace(&mut ready, true) {
+    ///         true => Poll::Ready(()),
+    ///         false => Poll::Pending,
+    ///     }).await
+    /// }
+    ///
+    /// let worker = WeakSelf::cyclic(|weak_self| Worker { weak_self, jobs: AtomicUsize::new(0) });
+    /// let mut task = pin!(worker.weak_self.run_async(|this| async move {
+    ///     loop {
+    ///         this.with(|this: Arc<Worker>| this.jobs.fetch_add(1, Ordering::SeqCst)).await;
+    ///         tick().await;
+    ///     }
+    /// }));
+    ///
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// assert_eq!(task.as_mut().poll(&mut cx), Poll::Pending);
+    /// assert_eq!(task.as_mut().poll(&mut cx), Poll::Pending);
+    /// assert_eq!(worker.jobs.load(Ordering::SeqCst), 2);
+    /// assert_eq!(Arc::strong_count(&worker), 1);
+    ///
+    /// drop(worker);
+    /// assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(None));
+    ///```
+    pub fn run_async<F, Fut>(&self, f: F) -> RunAsync<T, P, Fut>
+        where F: FnOnce(Handle<T, P>) -> Fut,
+              Fut: Future
+    {
+        let weak = self.get();
+        RunAsync {
+            task: Some(f(Handle { weak: weak.clone() })),
+            weak,
+        }
+    }
+}
+
+/// Future returned by [`WeakSelf::run`]
+#[must_use = "futures do nothing unless polled"]
+pub struct Run<T: ?Sized, P: SharedPtr<T>, F> {
+    weak: P::Weak,
+    f: F,
+}
+
+// the closure is never pinned, it is only called through a plain mutable reference
+impl<T: ?Sized, P: SharedPtr<T>, F> Unpin for Run<T, P, F> {}
+
+impl<T: ?Sized, P: SharedPtr<T>, R, F> Future for Run<T, P, F>
+    where F: FnMut(P::Strong, &mut Context<'_>) -> Poll<R>
+{
+    type Output = Option<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        let this = self.get_mut();
+        match P::upgrade(&this.weak) {
+            Some(content) => (this.f)(content, cx).map(Some),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// Future returned by [`WeakSelf::run_async`]
+#[must_use = "futures do nothing unless polled"]
+pub struct RunAsync<T: ?Sized, P: SharedPtr<T>, Fut> {
+    weak: P::Weak,
+    /// the task, dropped once the content has been dropped
+    task: Option<Fut>,
+}
+
+impl<T: ?Sized, P: SharedPtr<T>, Fut: Future> Future for RunAsync<T, P, Fut> {
+    type Output = Option<Fut::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Fut::Output>> {
+        // SAFETY: the task is pinned structurally, it is never moved out of its Option. weak is
+        // not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut task = unsafe { Pin::new_unchecked(&mut this.task) };
+        if P::weak_strong_count(&this.weak) > 0 {
+            if let Some(running) = task.as_mut().as_pin_mut() {
+                if let Poll::Ready(output) = running.poll(cx) {
+                    task.set(None);
+                    return Poll::Ready(Some(output));
+                }
+            }
+            // the task may be pending because the content has been dropped meanwhile
+            if P::weak_strong_count(&this.weak) > 0 {
+                return Poll::Pending;
+            }
+        }
+        task.set(None);
+        Poll::Ready(None)
+    }
+}
+
+/// Access to the content for the task of [`WeakSelf::run_async`], which never keeps the content
+/// alive across an `.await`
+pub struct Handle<T: ?Sized, P: SharedPtr<T>> {
+    weak: P::Weak,
+}
+
+impl<T: ?Sized, P: SharedPtr<T>> Handle<T, P> {
+    /// Run a step of the task with an Arc<T> to the content, which is released when the closure
+    /// returns.
+    ///
+    /// The returned future never completes if the content has been dropped, which makes
+    /// [`WeakSelf::run_async`] resolve to None.
+    pub fn with<R, F>(&self, f: F) -> With<'_, T, P, F>
+        where F: FnOnce(P::Strong) -> R
+    {
+        With {
+            handle: self,
+            f: Some(f),
+        }
+    }
+}
+
+/// Future returned by [`Handle::with`]
+#[must_use = "futures do nothing unless polled"]
+pub struct With<'a, T: ?Sized, P: SharedPtr<T>, F> {
+    handle: &'a Handle<T, P>,
+    f: Option<F>,
+}
+
+// the closure is never pinned, it is only moved out to be called
+impl<'a, T: ?Sized, P: SharedPtr<T>, F> Unpin for With<'a, T, P, F> {}
+
+impl<'a, T: ?Sized, P: SharedPtr<T>, R, F> Future for With<'a, T, P, F>
+    where F: FnOnce(P::Strong) -> R
+{
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<R> {
+        let this = self.get_mut();
+        match P::upgrade(&this.handle.weak) {
+            Some(content) => {
+                let f = this.f.take().expect("With polled after completion");
+                Poll::Ready(f(content))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use alloc::boxed::Box;
+use alloc::vec::{self, Vec};
+use core::ptr;
+
+use crate::sync::{AtomicPtr, Ordering};
+
+pub(crate) type Hook = Box<dyn FnOnce() + Send>;
+
+struct Node {
+    hook: Hook,
+    next: *mut Node,
+}
+
+/// Lock-free list of hooks which run once when the list is dropped.
+///
+/// Hooks are pushed onto an atomic stack, so they can be added from any thread without locking
+/// and without depending on std. Dropping requires exclusive access, which guarantees that no
+/// hook is added while the hooks run.
+///
+/// Public only to be named by [`crate::ptr::Atomic`], the module is private.
+pub struct DropHooks {
+    head: AtomicPtr<Node>,
+}
+
+impl DropHooks {
+    pub(crate) fn new() -> DropHooks {
+        DropHooks {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    pub(crate) fn push(&self, hook: Hook) {
+        let node = Box::into_raw(Box::new(Node { hook, next: ptr::null_mut() }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: the node is not shared until the compare_exchange succeeds
+            unsafe { (*node).next = head };
+            match self.head.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+impl Drop for DropHooks {
+    fn drop(&mut self) {
+        let mut hooks = Vec::new();
+        let mut node = self.head.load(Ordering::Acquire);
+        while !node.is_null() {
+            // SAFETY: nodes are only created by push and owned by the list
+            let Node { hook, next } = *unsafe { Box::from_raw(node) };
+            hooks.push(hook);
+            node = next;
+        }
+        // the stack holds the most recent hook first, run them in registration order
+        hooks.reverse();
+        run(hooks);
+    }
+}
+
+/// Run the hooks in order, also the ones after a panicking hook.
+pub(crate) fn run<H: FnOnce()>(hooks: Vec<H>) {
+    let mut remaining = Remaining(hooks.into_iter());
+    for hook in &mut remaining.0 {
+        hook();
+    }
+}
+
+/// Hooks not run yet, which still run if a hook panics.
+///
+/// A second panicking hook aborts, like any panic while unwinding.
+struct Remaining<H: FnOnce()>(vec::IntoIter<H>);
+
+impl<H: FnOnce()> Drop for Remaining<H> {
+    fn drop(&mut self) {
+        for hook in &mut self.0 {
+            hook();
+        }
+    }
+}
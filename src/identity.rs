@@ -0,0 +1,76 @@
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+use crate::ptr::SharedPtr;
+use crate::WeakSelf;
+
+/// Pointer identity of the content, without cloning the Weak<T> pointer.
+///
+/// An uninitialized WeakSelf<T> has no identity: it is never the same as any content, and all
+/// uninitialized WeakSelf<T> compare equal to each other and less than initialized ones.
+impl<T: ?Sized, P: SharedPtr<T>> WeakSelf<T, P> {
+    /// get a raw pointer to the content, or None if not yet initialized.
+    ///
+    /// The pointer dangles once the content has been dropped.
+    pub fn as_ptr(&self) -> Option<*const T> {
+        self.try_get().map(P::as_ptr)
+    }
+
+    /// true if other points to the content of this WeakSelf<T>
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>
+    /// }
+    ///
+    /// let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self });
+    /// let bar = WeakSelf::cyclic(|weak_self| Foo { weak_self });
+    /// assert!(foo.weak_self.is(&foo));
+    /// assert!(!foo.weak_self.is(&bar));
+    /// assert!(!WeakSelf::new().is(&foo));
+    ///```
+    pub fn is(&self, other: &P::Strong) -> bool {
+        self.addr() == Some(&**other as *const T as *const ())
+    }
+
+    /// true if both WeakSelf<T> point to the same content, or are both uninitialized
+    pub fn ptr_eq(&self, other: &WeakSelf<T, P>) -> bool {
+        self.addr() == other.addr()
+    }
+
+    /// address of the content, ignoring the metadata of unsized types
+    fn addr(&self) -> Option<*const ()> {
+        self.as_ptr().map(|ptr| ptr as *const ())
+    }
+}
+
+/// Compares by pointer identity, see [`WeakSelf::ptr_eq`]
+impl<T: ?Sized, P: SharedPtr<T>> PartialEq for WeakSelf<T, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl<T: ?Sized, P: SharedPtr<T>> Eq for WeakSelf<T, P> {}
+
+/// Orders by the address of the content, uninitialized first.
+impl<T: ?Sized, P: SharedPtr<T>> PartialOrd for WeakSelf<T, P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized, P: SharedPtr<T>> Ord for WeakSelf<T, P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.addr().cmp(&other.addr())
+    }
+}
+
+/// Hashes the address of the content. Note that initializing a WeakSelf<T> changes its hash.
+impl<T: ?Sized, P: SharedPtr<T>> Hash for WeakSelf<T, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
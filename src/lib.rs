@@ -1,6 +1,48 @@
-use std::cell::UnsafeCell;
-use std::fmt;
-use std::sync::{Arc, Weak};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::sync::{Arc, Weak};
+use core::fmt;
+
+use ptr::{DropHook, SharedPtr, StdArc, Storage};
+use storage::{HookList, SetOnce};
+
+mod callback;
+#[cfg(feature = "std")]
+pub mod emitter;
+mod error;
+pub mod future;
+mod hooks;
+mod identity;
+mod once;
+mod pending;
+pub mod ptr;
+pub mod rc;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "std")]
+pub mod set;
+mod storage;
+mod sync;
+#[cfg(feature = "std")]
+pub mod tree;
+#[cfg(feature = "std")]
+mod wait;
+
+pub use error::{InitError, WeakSelfError};
+pub use pending::PendingSelf;
+
+/// Derive macro generating [`HasWeakSelf`] and an `into_arc` constructor, see the `weak-self-derive` crate
+#[cfg(feature = "derive")]
+pub use weak_self_derive::WeakSelf;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::sync::Arc;
+}
 
 ///WeakSelf is simple way to have a Weak pointer inside a data structure pointing to itself.
 ///
@@ -52,11 +94,35 @@ use std::sync::{Arc, Weak};
 ///
 ///```
 ///
+///## Pointer kinds
+///
+///By default WeakSelf<T> points with [`std::sync::Arc`]. The second type parameter selects another
+///[`SharedPtr`] kind, e.g. `WeakSelf<T, StdRc>` points with [`std::rc::Rc`]. Single-threaded kinds
+///store their state without atomics:
+///
+///```rust
+/// use weak_self::WeakSelf;
+/// use weak_self::ptr::StdRc;
+/// use std::rc::Rc;
+/// pub struct Foo {
+///     weak_self: WeakSelf<Foo, StdRc>
+/// }
+///
+/// let foo = Rc::new(Foo { weak_self: WeakSelf::default() });
+/// foo.weak_self.init(&foo);
+/// assert!(Rc::ptr_eq(&foo, &foo.weak_self.arc()));
+///```
 ///
 ///
 ///## Dependencies
 ///
-///This package depends on std only
+///This package has no required dependencies. Its default `std` feature uses the standard library,
+///without it the crate is `no_std` and only needs `alloc`:
+///
+///```toml
+///[dependencies]
+///weak-self = { version = "1.0.2", default-features = false }
+///```
 ///
 ///## Usage
 ///
@@ -64,7 +130,7 @@ use std::sync::{Arc, Weak};
 ///
 ///```toml
 ///[dependencies]
-///weakself = "1.0.2"
+///weak-self = "1.0.2"
 ///```
 ///
 ///
@@ -73,67 +139,471 @@ use std::sync::{Arc, Weak};
 ///Licensed under the terms of MIT license and the Apache License (Version 2.0).
 ///
 ///See [LICENSE-MIT](LICENSE-MIT) and [LICENSE-APACHE](LICENSE-APACHE) for details.
-///
-pub struct WeakSelf<T: ?Sized> {
-    cell: UnsafeCell<Option<Weak<T>>>
+pub struct WeakSelf<T: ?Sized, P: SharedPtr<T> = StdArc> {
+    cell: <P::Storage as Storage>::Cell<P::Weak>,
+    hooks: <P::Storage as Storage>::Hooks,
 }
 
 impl<T: ?Sized> WeakSelf<T> {
     /// Constructs a new empty WeakSelf<T>
+    ///
+    /// Use [`WeakSelf::default`] to construct a WeakSelf<T, P> for other pointer kinds.
     pub fn new() -> WeakSelf<T> {
         WeakSelf {
-            cell: UnsafeCell::new(None)
+            cell: SetOnce::new(),
+            hooks: HookList::new(),
         }
     }
 
+    /// Initialize the WeakSelf<T> with an Arc<U> to a value of another type, usually a concrete type
+    /// implementing the trait T.
+    ///
+    /// The closure converts the Weak<U> pointer to a Weak<T> pointer, which is an unsized coercion
+    /// like `|weak| weak` in the common case of T being `dyn Trait`.
+    ///
+    /// Note: content must point be the only existing Arc, otherwise this method will panic.
+    /// It also panics if the closure returns a pointer to other content.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::{Arc, Weak};
+    ///
+    /// pub trait Listener {
+    ///     fn name(&self) -> &str;
+    /// }
+    ///
+    /// pub struct Button {
+    ///     weak_self: WeakSelf<dyn Listener + Send + Sync>,
+    /// }
+    ///
+    /// impl Listener for Button {
+    ///     fn name(&self) -> &str {
+    ///         "button"
+    ///     }
+    /// }
+    ///
+    /// let button = Arc::new(Button { weak_self: WeakSelf::new() });
+    /// button.weak_self.init_coerced(&button, |weak| weak);
+    ///
+    /// let listener: Weak<dyn Listener + Send + Sync> = button.weak_self.get();
+    /// assert_eq!(listener.upgrade().unwrap().name(), "button");
+    ///```
+    pub fn init_coerced<U: ?Sized, F>(&self, content: &Arc<U>, coerce: F)
+        where F: FnOnce(Weak<U>) -> Weak<T>
+    {
+        if let Err(err) = self.try_init_coerced(content, coerce) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Try to initialize the WeakSelf<T> with an Arc<U> to a value of another type, see
+    /// [`WeakSelf::init_coerced`].
+    ///
+    /// Fails like [`WeakSelf::try_init`], the closure is only called if the checks succeed.
+    /// Fails with [`InitError::CoercedToOtherContent`] if the closure returns a pointer to other
+    /// content.
+    ///
+    ///```rust
+    /// use weak_self::{InitError, WeakSelf};
+    /// use std::sync::Arc;
+    ///
+    /// let weak_self = WeakSelf::<dyn Send + Sync>::new();
+    /// let content = Arc::new(1);
+    /// let other: Arc<dyn Send + Sync> = Arc::new(2);
+    /// let result = weak_self.try_init_coerced(&content, |_| Arc::downgrade(&other));
+    /// assert_eq!(result, Err(InitError::CoercedToOtherContent));
+    ///
+    /// assert_eq!(weak_self.try_init_coerced(&content, |weak| weak), Ok(()));
+    ///```
+    pub fn try_init_coerced<U: ?Sized, F>(&self, content: &Arc<U>, coerce: F) -> Result<(), InitError>
+        where F: FnOnce(Weak<U>) -> Weak<T>
+    {
+        self.check_init(Arc::strong_count(content), Arc::weak_count(content))?;
+        let weak = coerce(Arc::downgrade(content));
+        if weak.as_ptr() as *const () != Arc::as_ptr(content) as *const () {
+            return Err(InitError::CoercedToOtherContent);
+        }
+        self.cell.set(weak).map_err(|_| InitError::AlreadyInitialized)
+    }
+}
+
+impl<T: ?Sized, P: SharedPtr<T>> WeakSelf<T, P> {
+    /// Constructs a WeakSelf<T> that is already initialized with the given Weak<T>
+    fn from_weak(weak: P::Weak) -> WeakSelf<T, P> {
+        WeakSelf {
+            cell: SetOnce::with_value(weak),
+            hooks: HookList::new(),
+        }
+    }
 
     /// Initialize the WeakSelf<T> with an Arc.
     ///
     /// Note: content must point be the only existing Arc, otherwise this method will panic
-    pub fn init(&self, content: &Arc<T>) {
-        if Arc::strong_count(content) != 1 || Arc::weak_count(content) != 0 {
-            panic!("Exclusive access to Arc<T> is required while initializing WeakSelf<T>");
+    ///
+    /// The WeakSelf<T> can be initialized only once. If several threads race to initialize it,
+    /// exactly one of them wins and all others panic. See [`WeakSelf::try_init`] for a non-panicking variant.
+    pub fn init(&self, content: &P::Strong) {
+        if let Err(err) = self.try_init(content) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Try to initialize the WeakSelf<T> with an Arc.
+    ///
+    /// Fails if the WeakSelf<T> is already initialized, or if content is not the only existing
+    /// strong or weak reference to its value. If several threads race to initialize it, exactly
+    /// one of them succeeds and all others get [`InitError::AlreadyInitialized`].
+    ///
+    ///```rust
+    /// use weak_self::{InitError, WeakSelf};
+    /// use std::sync::Arc;
+    ///
+    /// let weak_self = WeakSelf::new();
+    /// let content = Arc::new(42);
+    /// let other = content.clone();
+    /// assert_eq!(weak_self.try_init(&content), Err(InitError::StrongReferences(1)));
+    ///
+    /// drop(other);
+    /// assert_eq!(weak_self.try_init(&content), Ok(()));
+    /// assert_eq!(weak_self.try_init(&content), Err(InitError::AlreadyInitialized));
+    ///```
+    pub fn try_init(&self, content: &P::Strong) -> Result<(), InitError> {
+        self.check_init(P::strong_count(content), P::weak_count(content))?;
+        self.cell.set(P::downgrade(content)).map_err(|_| InitError::AlreadyInitialized)
+    }
+
+    /// check that the WeakSelf<T> is empty and the content is not shared with anybody else
+    fn check_init(&self, strong: usize, weak: usize) -> Result<(), InitError> {
+        if self.cell.get().is_some() {
+            return Err(InitError::AlreadyInitialized);
+        }
+        if strong != 1 {
+            return Err(InitError::StrongReferences(strong - 1));
         }
-        let weak = Arc::downgrade(content);
-        unsafe {
-            *self.cell.get() = Some(weak);
+        if weak != 0 {
+            return Err(InitError::WeakReferences(weak));
         }
+        Ok(())
     }
 
     /// get Some Weak<T> pointer to the content, or None if not yet initialized
-    pub fn try_get(&self) -> Option<&Weak<T>> {
-        unsafe {
-            match *self.cell.get() {
-                Some(ref weak) => Some(&weak),
-                None => None
-            }
-        }
+    pub fn try_get(&self) -> Option<&P::Weak> {
+        self.cell.get()
     }
 
     /// get a Weak<T> pointer to the content, or panic if not yet initialized
-    pub fn get(&self) -> Weak<T> {
+    pub fn get(&self) -> P::Weak {
         self.try_get().expect("expected WeakSelf to be initialized").clone()
     }
+
+    /// Register a hook which runs when this WeakSelf<T> is dropped, usually as part of dropping
+    /// the value holding it.
+    ///
+    /// Hooks can be registered from any thread. Each hook runs exactly once, in registration
+    /// order, on the thread dropping the WeakSelf<T>. By then the content can no longer be
+    /// upgraded. If a hook panics, the hooks after it still run while the panic unwinds.
+    ///
+    /// Hooks must be `Send` for pointer kinds which can cross threads. Single-threaded kinds like
+    /// [`rc::WeakSelf`] accept any `FnOnce()`, see [`DropHook`].
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::{Arc, Mutex};
+    /// pub struct Session {
+    ///     weak_self: WeakSelf<Session>
+    /// }
+    ///
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let session = WeakSelf::cyclic(|weak_self| Session { weak_self });
+    /// for name in ["first", "second"] {
+    ///     let log = log.clone();
+    ///     session.weak_self.on_drop(move || log.lock().unwrap().push(name));
+    /// }
+    ///
+    /// drop(session);
+    /// assert_eq!(*log.lock().unwrap(), ["first", "second"]);
+    ///```
+    pub fn on_drop<F>(&self, hook: F)
+        where F: DropHook<P::Storage>
+    {
+        hook.push(&self.hooks);
+    }
+
+    /// get Some Arc<T> pointer to the content, or None if not yet initialized or already dropped
+    pub fn upgrade(&self) -> Option<P::Strong> {
+        self.try_get().and_then(P::upgrade)
+    }
+
+    /// get an Arc<T> pointer to the content, or an error telling why there is none
+    ///
+    ///```rust
+    /// use weak_self::{WeakSelf, WeakSelfError};
+    /// use std::sync::Arc;
+    ///
+    /// let weak_self = WeakSelf::new();
+    /// assert_eq!(weak_self.try_arc().unwrap_err(), WeakSelfError::NotInitialized);
+    ///
+    /// let content = Arc::new(42);
+    /// weak_self.init(&content);
+    /// assert_eq!(*weak_self.try_arc().unwrap(), 42);
+    ///
+    /// drop(content);
+    /// assert_eq!(weak_self.try_arc().unwrap_err(), WeakSelfError::Expired);
+    ///```
+    pub fn try_arc(&self) -> Result<P::Strong, WeakSelfError> {
+        let weak = self.try_get().ok_or(WeakSelfError::NotInitialized)?;
+        P::upgrade(weak).ok_or(WeakSelfError::Expired)
+    }
+
+    /// get an Arc<T> pointer to the content, or panic if not yet initialized or already dropped
+    pub fn arc(&self) -> P::Strong {
+        match self.try_arc() {
+            Ok(arc) => arc,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// true if the WeakSelf<T> has been initialized
+    pub fn is_initialized(&self) -> bool {
+        self.try_get().is_some()
+    }
+
+    /// true if the WeakSelf<T> has been initialized and its content has not been dropped yet
+    pub fn is_alive(&self) -> bool {
+        self.strong_count() > 0
+    }
+
+    /// number of strong pointers to the content, or 0 if not yet initialized or already dropped
+    pub fn strong_count(&self) -> usize {
+        self.try_get().map_or(0, P::weak_strong_count)
+    }
+
+    /// number of weak pointers to the content, not counting the one of this WeakSelf<T>.
+    ///
+    /// Returns 0 if not yet initialized or already dropped.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>
+    /// }
+    ///
+    /// let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self });
+    /// let other = foo.clone();
+    /// let weak = Arc::downgrade(&foo);
+    /// assert_eq!(foo.weak_self.strong_count(), 2);
+    /// assert_eq!(foo.weak_self.weak_count(), 1);
+    /// assert_eq!(format!("{:?}", foo.weak_self), r#"WeakSelf { state: "live", strong: 2, weak: 1 }"#);
+    ///```
+    pub fn weak_count(&self) -> usize {
+        self.try_get().map_or(0, |weak| P::weak_weak_count(weak).saturating_sub(1))
+    }
+}
+
+impl<T> WeakSelf<T> {
+    /// Constructs a new Arc<T> whose WeakSelf<T> is initialized while the value is being built.
+    ///
+    /// The closure receives an already initialized WeakSelf<T> to store in the new value.
+    /// Upgrading its Weak<T> pointer returns None until the closure has returned, so the
+    /// half-built value can never escape.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>
+    /// }
+    ///
+    /// impl Foo {
+    ///     pub fn new() -> Arc<Foo> {
+    ///         WeakSelf::cyclic(|weak_self| Foo { weak_self })
+    ///     }
+    /// }
+    ///
+    /// let foo = Foo::new();
+    /// assert!(Arc::ptr_eq(&foo, &foo.weak_self.get().upgrade().unwrap()));
+    ///```
+    pub fn cyclic<F>(build: F) -> Arc<T>
+        where F: FnOnce(WeakSelf<T>) -> T
+    {
+        Arc::new_cyclic(|weak| build(WeakSelf::from_weak(weak.clone())))
+    }
+
+    /// Fallible variant of [`WeakSelf::cyclic`].
+    ///
+    /// If the closure returns an error, no Arc<T> is created and the error is returned.
+    /// Weak<T> pointers handed out while building never upgrade in that case, on any thread.
+    ///
+    /// The error unwinds out of `Arc::new_cyclic`, so that no Arc<T> ever exists over a value that
+    /// has not been built. This requires the `std` feature and `panic = "unwind"`.
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>,
+    ///     port: u16,
+    /// }
+    ///
+    /// impl Foo {
+    ///     pub fn parse(port: &str) -> Result<Arc<Foo>, std::num::ParseIntError> {
+    ///         WeakSelf::try_cyclic(|weak_self| Ok(Foo { weak_self, port: port.parse()? }))
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Foo::parse("8080").unwrap().port, 8080);
+    /// assert!(Foo::parse("http").is_err());
+    ///```
+    #[cfg(all(feature = "std", panic = "unwind"))]
+    pub fn try_cyclic<E, F>(build: F) -> Result<Arc<T>, E>
+        where F: FnOnce(WeakSelf<T>) -> Result<T, E>
+    {
+        try_new_cyclic(|weak| build(WeakSelf::from_weak(weak.clone())))
+    }
+}
+
+impl<T: HasWeakSelf + Clone> WeakSelf<T> {
+    /// Clone a value into a new Arc<T>, with the WeakSelf<T> of the clone pointing to the clone.
+    ///
+    ///```rust
+    /// use weak_self::{HasWeakSelf, WeakSelf};
+    /// use std::sync::Arc;
+    /// #[derive(Clone)]
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>,
+    ///     value: u32,
+    /// }
+    ///
+    /// impl HasWeakSelf for Foo {
+    ///     fn weak_self(&self) -> &WeakSelf<Self> {
+    ///         &self.weak_self
+    ///     }
+    /// }
+    ///
+    /// let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self, value: 1 });
+    /// assert!(foo.as_ref().clone().weak_self.try_get().is_none());
+    ///
+    /// let copy = WeakSelf::clone_into_arc(&*foo);
+    /// assert!(!Arc::ptr_eq(&foo, &copy));
+    /// assert!(Arc::ptr_eq(&copy, &copy.shared_from_this()));
+    /// assert_eq!(copy.value, 1);
+    ///```
+    pub fn clone_into_arc(value: &T) -> Arc<T> {
+        PendingSelf::build(|_| value.clone())
+    }
 }
 
-unsafe impl<T: ?Sized + Sync + Send> Sync for WeakSelf<T> {}
+/// Panic payload carrying a failed build out of Arc::new_cyclic
+#[cfg(all(feature = "std", panic = "unwind"))]
+struct BuildFailed;
+
+/// Fallible Arc::new_cyclic: constructs a new Arc<T>, unless the closure returns an error.
+///
+/// An error unwinds out of Arc::new_cyclic, which then frees the allocation without ever setting
+/// its strong count. So, like with Arc::new_cyclic, the Weak<T> pointer passed to the closure does
+/// not upgrade until the Arc<T> has been constructed, and never if the closure returns an error.
+#[cfg(all(feature = "std", panic = "unwind"))]
+fn try_new_cyclic<T, E, F>(build: F) -> Result<Arc<T>, E>
+    where F: FnOnce(&Weak<T>) -> Result<T, E>
+{
+    use alloc::boxed::Box;
+    use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+    let mut error = None;
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        Arc::new_cyclic(|weak| match build(weak) {
+            Ok(value) => value,
+            Err(err) => {
+                error = Some(err);
+                resume_unwind(Box::new(BuildFailed))
+            }
+        })
+    }));
+    match result {
+        Ok(arc) => Ok(arc),
+        Err(payload) if payload.is::<BuildFailed>() => Err(error.take().expect("build error is stored")),
+        Err(payload) => resume_unwind(payload),
+    }
+}
+
+/// Trait for types holding a WeakSelf<Self>, similar to C++ `enable_shared_from_this`.
+///
+/// Implementors only provide access to their WeakSelf<Self>; the trait provides methods to get
+/// Weak<Self> and Arc<Self> pointers from a plain `&self`.
+///
+///```rust
+/// use weak_self::{HasWeakSelf, WeakSelf};
+/// use std::sync::Arc;
+/// pub struct Foo {
+///     weak_self: WeakSelf<Foo>
+/// }
+///
+/// impl HasWeakSelf for Foo {
+///     fn weak_self(&self) -> &WeakSelf<Self> {
+///         &self.weak_self
+///     }
+/// }
+///
+/// fn keep<T: HasWeakSelf>(content: &T) -> Arc<T> {
+///     content.shared_from_this()
+/// }
+///
+/// let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self });
+/// assert!(Arc::ptr_eq(&foo, &keep(&*foo)));
+///```
+pub trait HasWeakSelf {
+    /// get the WeakSelf<Self> of this value
+    fn weak_self(&self) -> &WeakSelf<Self>;
+
+    /// get a Weak<Self> pointer to this value, or panic if the WeakSelf<Self> is not yet initialized
+    fn weak_from_this(&self) -> Weak<Self> {
+        self.weak_self().get()
+    }
 
-unsafe impl<T: ?Sized + Sync + Send> Send for WeakSelf<T> {}
+    /// get an Arc<Self> pointer to this value, or panic if not yet initialized or already dropped
+    fn shared_from_this(&self) -> Arc<Self> {
+        self.weak_self().arc()
+    }
 
+    /// get an Arc<Self> pointer to this value, or an error telling why there is none
+    fn try_shared_from_this(&self) -> Result<Arc<Self>, WeakSelfError> {
+        self.weak_self().try_arc()
+    }
+}
 
-impl<T: ?Sized + fmt::Debug> fmt::Debug for WeakSelf<T> {
+/// Shows whether the WeakSelf<T> is uninitialized, live with its counts, or dangling
+impl<T: ?Sized, P: SharedPtr<T>> fmt::Debug for WeakSelf<T, P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.try_get() {
-            None => { write!(f, "Empty WeakSelf<T>") }
-            Some(weak) => fmt::Debug::fmt(weak, f),
+        if !self.is_initialized() {
+            f.debug_struct("WeakSelf").field("state", &"uninitialized").finish()
+        } else if self.is_alive() {
+            f.debug_struct("WeakSelf")
+                .field("state", &"live")
+                .field("strong", &self.strong_count())
+                .field("weak", &self.weak_count())
+                .finish()
+        } else {
+            f.debug_struct("WeakSelf").field("state", &"dangling").finish()
         }
     }
 }
 
 
-impl<T: ?Sized> Default for WeakSelf<T> {
+impl<T: ?Sized, P: SharedPtr<T>> Default for WeakSelf<T, P> {
     fn default() -> Self {
-        WeakSelf::new()
+        WeakSelf {
+            cell: SetOnce::new(),
+            hooks: HookList::new(),
+        }
+    }
+}
+
+/// Clones as an empty WeakSelf<T>, as the clone of a value is a different object than the
+/// original. Drop hooks are not cloned either. See [`WeakSelf::clone_into_arc`] to link the clone.
+impl<T: ?Sized, P: SharedPtr<T>> Clone for WeakSelf<T, P> {
+    fn clone(&self) -> Self {
+        WeakSelf::default()
     }
 }
 
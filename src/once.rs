@@ -0,0 +1,107 @@
+use core::mem::MaybeUninit;
+
+use crate::sync::{spin_loop, AtomicU8, Ordering, UnsafeCell};
+
+const EMPTY: u8 = 0;
+const RUNNING: u8 = 1;
+const READY: u8 = 2;
+const STATE: u8 = 0b011;
+/// flag set by threads waiting for the value, see crate::wait
+#[cfg(feature = "std")]
+const WAITERS: u8 = 0b100;
+
+/// A cell which can be written to only once, built on an atomic state machine so it does not
+/// depend on std.
+///
+/// The state moves from EMPTY to RUNNING for the one thread that wins the race to set the value,
+/// and from RUNNING to READY once the value has been written. Threads that lose the race spin
+/// while the winner moves the value into place, so a failed set is always followed by a
+/// successful get.
+///
+/// With the std feature, threads can also block until the value is set. They flag the state
+/// with WAITERS, which tells the winner to wake them up.
+///
+/// Public only to be named by [`crate::ptr::Atomic`], the module is private.
+pub struct OnceCell<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> OnceCell<T> {
+    pub(crate) fn new() -> OnceCell<T> {
+        OnceCell {
+            state: AtomicU8::new(EMPTY),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    pub(crate) fn with_value(value: T) -> OnceCell<T> {
+        OnceCell {
+            state: AtomicU8::new(READY),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) & STATE == READY {
+            // SAFETY: READY is only stored after the value has been written, and the value is
+            // never written again afterwards
+            Some(self.value.with(|value| unsafe { (*value).assume_init_ref() }))
+        } else {
+            None
+        }
+    }
+
+    /// set the value, or give it back if the cell has already been set
+    pub(crate) fn set(&self, value: T) -> Result<(), T> {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            if state & STATE != EMPTY {
+                while self.state.load(Ordering::Acquire) & STATE != READY {
+                    spin_loop();
+                }
+                return Err(value);
+            }
+            match self.state.compare_exchange_weak(state, state | RUNNING, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => break,
+                Err(current) => state = current,
+            }
+        }
+        // SAFETY: winning the compare_exchange grants exclusive access to the value, and
+        // readers do not touch it before they observe READY
+        self.value.with_mut(|cell| unsafe { (*cell).write(value) });
+        // moves from RUNNING to READY, keeping the WAITERS flag
+        let _previous = self.state.fetch_add(READY - RUNNING, Ordering::AcqRel);
+        #[cfg(feature = "std")]
+        if _previous & WAITERS != 0 {
+            crate::wait::notify(self.key());
+        }
+        Ok(())
+    }
+
+    /// Flag the cell as having waiters, returning true if the value has been set already
+    #[cfg(feature = "std")]
+    pub(crate) fn register_waiter(&self) -> bool {
+        self.state.fetch_or(WAITERS, Ordering::AcqRel) & STATE == READY
+    }
+
+    /// key identifying this cell while it is borrowed
+    #[cfg(feature = "std")]
+    pub(crate) fn key(&self) -> usize {
+        self as *const OnceCell<T> as usize
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if self.state.load(Ordering::Acquire) & STATE == READY {
+            // SAFETY: the value has been written and is dropped only once
+            self.value.with_mut(|value| unsafe { (*value).assume_init_drop() })
+        }
+    }
+}
+
+// SAFETY: the value is written by exactly one thread and only shared after that, like OnceLock
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+unsafe impl<T: Send> Send for OnceCell<T> {}
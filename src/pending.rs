@@ -0,0 +1,90 @@
+use alloc::sync::{Arc, Weak};
+
+use crate::HasWeakSelf;
+
+///Reservation of an Arc<T> under construction, which hands out Weak<T> pointers before the value
+///exists.
+///
+///The Weak<T> pointers do not upgrade until the value has been built, and never if building fails.
+///Once built, the value is moved into the reserved Arc<T> and its WeakSelf<T> field is linked to
+///it, so the field can simply be initialized with `WeakSelf::new()`.
+///
+///A PendingSelf<T> only lives while the value is being built, as Rust offers no way to keep an
+///allocation reserved across arbitrary code.
+///
+///```rust
+/// use weak_self::{HasWeakSelf, PendingSelf, WeakSelf};
+/// use std::sync::{Arc, Weak};
+/// pub struct Timer {
+///     owner: Weak<Server>,
+/// }
+///
+/// pub struct Server {
+///     weak_self: WeakSelf<Server>,
+///     timer: Timer,
+/// }
+///
+/// impl HasWeakSelf for Server {
+///     fn weak_self(&self) -> &WeakSelf<Self> {
+///         &self.weak_self
+///     }
+/// }
+///
+/// let server = PendingSelf::build(|pending| {
+///     let timer = Timer { owner: pending.weak() };
+///     assert!(timer.owner.upgrade().is_none());
+///     Server { weak_self: WeakSelf::new(), timer }
+/// });
+/// assert!(Arc::ptr_eq(&server, &server.timer.owner.upgrade().unwrap()));
+/// assert!(Arc::ptr_eq(&server, &server.shared_from_this()));
+///```
+pub struct PendingSelf<T: HasWeakSelf> {
+    weak: Weak<T>,
+}
+
+impl<T: HasWeakSelf> PendingSelf<T> {
+    /// Build a value into a new Arc<T>, passing a PendingSelf<T> to the closure.
+    ///
+    /// Panics if the WeakSelf<T> of the value is initialized with another Arc<T>.
+    pub fn build<F>(build: F) -> Arc<T>
+        where F: FnOnce(&PendingSelf<T>) -> T
+    {
+        Arc::new_cyclic(|weak| {
+            let pending = PendingSelf { weak: weak.clone() };
+            let value = build(&pending);
+            pending.link(value)
+        })
+    }
+
+    /// Fallible variant of [`PendingSelf::build`].
+    ///
+    /// If the closure returns an error, no Arc<T> is created and the error is returned. Weak<T>
+    /// pointers handed out by the PendingSelf<T> never upgrade in that case, on any thread.
+    ///
+    /// Like [`WeakSelf::try_cyclic`](crate::WeakSelf::try_cyclic), this requires the `std`
+    /// feature and `panic = "unwind"`.
+    #[cfg(all(feature = "std", panic = "unwind"))]
+    pub fn try_build<E, F>(build: F) -> Result<Arc<T>, E>
+        where F: FnOnce(&PendingSelf<T>) -> Result<T, E>
+    {
+        crate::try_new_cyclic(|weak| {
+            let pending = PendingSelf { weak: weak.clone() };
+            build(&pending).map(|value| pending.link(value))
+        })
+    }
+
+    /// get a Weak<T> pointer to the value, which upgrades once the value has been built
+    pub fn weak(&self) -> Weak<T> {
+        self.weak.clone()
+    }
+
+    /// link the WeakSelf<T> of the value to the reserved Arc<T>
+    fn link(self, value: T) -> T {
+        if let Err(weak) = value.weak_self().cell.set(self.weak) {
+            if !value.weak_self().try_get().is_some_and(|linked| linked.ptr_eq(&weak)) {
+                panic!("WeakSelf<T> of a value built by PendingSelf<T> must not point to another Arc<T>");
+            }
+        }
+        value
+    }
+}
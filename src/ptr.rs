@@ -0,0 +1,269 @@
+//! Shared pointer kinds a [`WeakSelf`](crate::WeakSelf) can point with.
+
+use alloc::boxed::Box;
+use core::ops::Deref;
+
+use crate::storage::{HookList, Sealed, SetOnce};
+
+/// A kind of reference counted shared pointer, described by its strong and weak pointer types.
+///
+/// [`WeakSelf<T, P>`](crate::WeakSelf) stores a `P::Weak` and hands out `P::Strong` pointers.
+/// Built-in kinds are [`StdArc`] (the default) and [`StdRc`]. With the `portable-atomic-util`
+/// feature, [`PortableArc`] supports `portable_atomic_util::Arc`.
+///
+/// Only pointers with weak references can be supported, so e.g. `triomphe::Arc` can not implement
+/// this trait.
+pub trait SharedPtr<T: ?Sized> {
+    /// Strong pointer type, like Arc<T>
+    type Strong: Clone + Deref<Target = T>;
+    /// Weak pointer type, like Weak<T>
+    type Weak: Clone;
+    /// How a WeakSelf<T, Self> stores its state: [`Atomic`] for pointers which can cross threads,
+    /// [`Local`] for single-threaded ones
+    type Storage: Storage;
+
+    /// create a new Weak pointer to the content of a strong pointer
+    fn downgrade(this: &Self::Strong) -> Self::Weak;
+
+    /// get a strong pointer to the content, or None if it has already been dropped
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong>;
+
+    /// number of strong pointers to the content of this strong pointer
+    fn strong_count(this: &Self::Strong) -> usize;
+
+    /// number of weak pointers to the content of this strong pointer
+    fn weak_count(this: &Self::Strong) -> usize;
+
+    /// number of strong pointers to the content of this weak pointer
+    fn weak_strong_count(weak: &Self::Weak) -> usize;
+
+    /// number of weak pointers to the content of this weak pointer, or 0 if no strong pointers remain
+    fn weak_weak_count(weak: &Self::Weak) -> usize;
+
+    /// true if both weak pointers point to the same allocation
+    fn ptr_eq(a: &Self::Weak, b: &Self::Weak) -> bool;
+
+    /// raw pointer to the content of a weak pointer, which may dangle if the content has been dropped
+    fn as_ptr(weak: &Self::Weak) -> *const T;
+}
+
+/// Storage of the Weak pointer and the drop hooks of a [`WeakSelf`](crate::WeakSelf), selected
+/// by [`SharedPtr::Storage`]. Only [`Atomic`] and [`Local`] implement it.
+pub trait Storage: Sealed {
+    #[doc(hidden)]
+    type Cell<V>: SetOnce<V>;
+    #[doc(hidden)]
+    type Hooks: HookList;
+}
+
+/// [`Storage`] for pointers which can cross threads: an atomic once cell, which other threads
+/// can also wait for, and a lock-free hook list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Atomic;
+
+impl Sealed for Atomic {}
+
+impl Storage for Atomic {
+    type Cell<V> = crate::once::OnceCell<V>;
+    type Hooks = crate::hooks::DropHooks;
+}
+
+/// [`Storage`] for single-threaded pointers like [`alloc::rc::Rc`]: a [`core::cell::OnceCell`]
+/// and a plain hook list, without the cost of atomics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Local;
+
+impl Sealed for Local {}
+
+impl Storage for Local {
+    type Cell<V> = core::cell::OnceCell<V>;
+    type Hooks = crate::storage::LocalHooks;
+}
+
+/// Closures a [`WeakSelf`](crate::WeakSelf) with the given [`Storage`] accepts as drop hooks, see
+/// [`WeakSelf::on_drop`](crate::WeakSelf::on_drop).
+///
+/// With [`Atomic`] storage hooks must be `Send`, as they run on whichever thread drops the
+/// WeakSelf. With [`Local`] storage any `FnOnce()` works, so hooks can capture `Rc` or `Cell`
+/// state of the owning thread.
+pub trait DropHook<S: Storage>: 'static {
+    #[doc(hidden)]
+    fn push(self, hooks: &S::Hooks);
+}
+
+impl<F: FnOnce() + Send + 'static> DropHook<Atomic> for F {
+    fn push(self, hooks: &crate::hooks::DropHooks) {
+        hooks.push(Box::new(self));
+    }
+}
+
+impl<F: FnOnce() + 'static> DropHook<Local> for F {
+    fn push(self, hooks: &crate::storage::LocalHooks) {
+        hooks.push(Box::new(self));
+    }
+}
+
+/// [`SharedPtr`] kind for [`alloc::sync::Arc`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct StdArc;
+
+impl<T: ?Sized> SharedPtr<T> for StdArc {
+    type Strong = alloc::sync::Arc<T>;
+    type Weak = alloc::sync::Weak<T>;
+    type Storage = Atomic;
+
+    fn downgrade(this: &Self::Strong) -> Self::Weak {
+        alloc::sync::Arc::downgrade(this)
+    }
+
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong> {
+        weak.upgrade()
+    }
+
+    fn strong_count(this: &Self::Strong) -> usize {
+        alloc::sync::Arc::strong_count(this)
+    }
+
+    fn weak_count(this: &Self::Strong) -> usize {
+        alloc::sync::Arc::weak_count(this)
+    }
+
+    fn weak_strong_count(weak: &Self::Weak) -> usize {
+        weak.strong_count()
+    }
+
+    fn weak_weak_count(weak: &Self::Weak) -> usize {
+        weak.weak_count()
+    }
+
+    fn ptr_eq(a: &Self::Weak, b: &Self::Weak) -> bool {
+        a.ptr_eq(b)
+    }
+
+    fn as_ptr(weak: &Self::Weak) -> *const T {
+        weak.as_ptr()
+    }
+}
+
+/// [`SharedPtr`] kind for [`alloc::rc::Rc`]
+///
+/// A `WeakSelf<T, StdRc>` is neither `Send` nor `Sync`, and uses [`Local`] storage, so it cannot
+/// be waited for. [`crate::rc::WeakSelf`] wraps it, so that `WeakSelf::new()` does not need to
+/// name the pointer kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct StdRc;
+
+impl<T: ?Sized> SharedPtr<T> for StdRc {
+    type Strong = alloc::rc::Rc<T>;
+    type Weak = alloc::rc::Weak<T>;
+    type Storage = Local;
+
+    fn downgrade(this: &Self::Strong) -> Self::Weak {
+        alloc::rc::Rc::downgrade(this)
+    }
+
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong> {
+        weak.upgrade()
+    }
+
+    fn strong_count(this: &Self::Strong) -> usize {
+        alloc::rc::Rc::strong_count(this)
+    }
+
+    fn weak_count(this: &Self::Strong) -> usize {
+        alloc::rc::Rc::weak_count(this)
+    }
+
+    fn weak_strong_count(weak: &Self::Weak) -> usize {
+        weak.strong_count()
+    }
+
+    fn weak_weak_count(weak: &Self::Weak) -> usize {
+        weak.weak_count()
+    }
+
+    fn ptr_eq(a: &Self::Weak, b: &Self::Weak) -> bool {
+        a.ptr_eq(b)
+    }
+
+    fn as_ptr(weak: &Self::Weak) -> *const T {
+        weak.as_ptr()
+    }
+}
+
+/// [`SharedPtr`] kind for `portable_atomic_util::Arc`, for targets without native atomic
+/// reference counting
+///
+/// As `portable_atomic_util::Weak` can not provide a pointer to unsized content, only sized
+/// types are supported.
+///
+///```rust
+/// use portable_atomic_util::Arc;
+/// use weak_self::{InitError, WeakSelf};
+/// use weak_self::ptr::PortableArc;
+/// pub struct Foo {
+///     weak_self: WeakSelf<Foo, PortableArc>
+/// }
+///
+/// let foo = Arc::new(Foo { weak_self: WeakSelf::default() });
+/// let weak = Arc::downgrade(&foo);
+/// assert_eq!(foo.weak_self.try_init(&foo), Err(InitError::WeakReferences(1)));
+/// drop(weak);
+/// foo.weak_self.init(&foo);
+/// assert!(foo.weak_self.is(&foo));
+/// assert!(Arc::ptr_eq(&foo, &foo.weak_self.upgrade().unwrap()));
+/// assert_eq!(foo.weak_self.strong_count(), 1);
+/// assert_eq!(foo.weak_self.weak_count(), 0);
+///
+/// let other = foo.clone();
+/// let weak = Arc::downgrade(&foo);
+/// assert_eq!(foo.weak_self.strong_count(), 2);
+/// assert_eq!(foo.weak_self.weak_count(), 1);
+///
+/// let weak_self = WeakSelf::<Foo, PortableArc>::default();
+/// assert!(!weak_self.is(&foo));
+/// drop((foo, other));
+/// assert!(weak.upgrade().is_none());
+///```
+#[cfg(feature = "portable-atomic-util")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PortableArc;
+
+#[cfg(feature = "portable-atomic-util")]
+impl<T> SharedPtr<T> for PortableArc {
+    type Strong = portable_atomic_util::Arc<T>;
+    type Weak = portable_atomic_util::Weak<T>;
+    type Storage = Atomic;
+
+    fn downgrade(this: &Self::Strong) -> Self::Weak {
+        portable_atomic_util::Arc::downgrade(this)
+    }
+
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong> {
+        weak.upgrade()
+    }
+
+    fn strong_count(this: &Self::Strong) -> usize {
+        portable_atomic_util::Arc::strong_count(this)
+    }
+
+    fn weak_count(this: &Self::Strong) -> usize {
+        portable_atomic_util::Arc::weak_count(this)
+    }
+
+    fn weak_strong_count(weak: &Self::Weak) -> usize {
+        weak.strong_count()
+    }
+
+    fn weak_weak_count(weak: &Self::Weak) -> usize {
+        weak.weak_count()
+    }
+
+    fn ptr_eq(a: &Self::Weak, b: &Self::Weak) -> bool {
+        a.ptr_eq(b)
+    }
+
+    fn as_ptr(weak: &Self::Weak) -> *const T {
+        weak.as_ptr()
+    }
+}
@@ -0,0 +1,164 @@
+//! Single-threaded WeakSelf backed by [`Rc`] and [`rc::Weak`](alloc::rc::Weak).
+
+use alloc::rc::{Rc, Weak};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+use crate::ptr::StdRc;
+use crate::WeakSelfError;
+
+///WeakSelf for single-threaded data structures, holding a [`alloc::rc::Weak`] pointer to itself.
+///
+///This is a thin wrapper of [`crate::WeakSelf<T, StdRc>`], so `WeakSelf::new()` picks [`Rc`]
+///without naming the pointer kind. It dereferences to the wrapped WeakSelf for all other methods,
+///like `init`, `get`, `upgrade` or `strong_count`. It is neither `Send` nor `Sync`, and stores its
+///state in [`Local`](crate::ptr::Local) storage, a [`core::cell::OnceCell`] and a plain hook list
+///without any atomics.
+///
+///```rust
+/// use weak_self::rc::WeakSelf;
+/// use std::rc::{Rc, Weak};
+/// pub struct Foo {
+///     weak_self: WeakSelf<Foo>
+/// }
+///
+/// impl Foo {
+///     pub fn new() -> Rc<Foo> {
+///         let foo = Rc::new(Foo{
+///             weak_self: WeakSelf::new()
+///         });
+///         foo.weak_self.init(&foo);
+///         foo
+///     }
+///
+///     fn weak(&self) -> Weak<Self> {
+///         self.weak_self.get()
+///     }
+/// }
+///
+/// let foo = Foo::new();
+/// assert!(Rc::ptr_eq(&foo, &foo.weak_self.arc()));
+///```
+pub struct WeakSelf<T: ?Sized>(crate::WeakSelf<T, StdRc>);
+
+impl<T: ?Sized> WeakSelf<T> {
+    /// Constructs a new empty WeakSelf<T>
+    pub fn new() -> WeakSelf<T> {
+        WeakSelf(crate::WeakSelf::default())
+    }
+}
+
+impl<T> WeakSelf<T> {
+    /// Constructs a new Rc<T> whose WeakSelf<T> is initialized while the value is being built,
+    /// see [`crate::WeakSelf::cyclic`].
+    ///
+    ///```rust
+    /// use weak_self::rc::WeakSelf;
+    /// use std::rc::Rc;
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>
+    /// }
+    ///
+    /// let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self });
+    /// assert!(Rc::ptr_eq(&foo, &foo.weak_self.get().upgrade().unwrap()));
+    ///```
+    pub fn cyclic<F>(build: F) -> Rc<T>
+        where F: FnOnce(WeakSelf<T>) -> T
+    {
+        Rc::new_cyclic(|weak| build(WeakSelf(crate::WeakSelf::from_weak(weak.clone()))))
+    }
+}
+
+/// Trait for types holding an rc::WeakSelf<Self>, the single-threaded counterpart of
+/// [`crate::HasWeakSelf`].
+///
+///```rust
+/// use weak_self::rc::{HasWeakSelf, WeakSelf};
+/// use std::rc::Rc;
+/// pub struct Foo {
+///     weak_self: WeakSelf<Foo>
+/// }
+///
+/// impl HasWeakSelf for Foo {
+///     fn weak_self(&self) -> &WeakSelf<Self> {
+///         &self.weak_self
+///     }
+/// }
+///
+/// let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self });
+/// assert!(Rc::ptr_eq(&foo, &foo.shared_from_this()));
+///```
+pub trait HasWeakSelf {
+    /// get the rc::WeakSelf<Self> of this value
+    fn weak_self(&self) -> &WeakSelf<Self>;
+
+    /// get a Weak<Self> pointer to this value, or panic if the WeakSelf<Self> is not yet initialized
+    fn weak_from_this(&self) -> Weak<Self> {
+        self.weak_self().get()
+    }
+
+    /// get an Rc<Self> pointer to this value, or panic if not yet initialized or already dropped
+    fn shared_from_this(&self) -> Rc<Self> {
+        self.weak_self().arc()
+    }
+
+    /// get an Rc<Self> pointer to this value, or an error telling why there is none
+    fn try_shared_from_this(&self) -> Result<Rc<Self>, WeakSelfError> {
+        self.weak_self().try_arc()
+    }
+}
+
+impl<T: ?Sized> Deref for WeakSelf<T> {
+    type Target = crate::WeakSelf<T, StdRc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for WeakSelf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl<T: ?Sized> Default for WeakSelf<T> {
+    fn default() -> Self {
+        WeakSelf::new()
+    }
+}
+
+/// Clones as an empty WeakSelf<T>, as the clone of a value is a different object than the original
+impl<T: ?Sized> Clone for WeakSelf<T> {
+    fn clone(&self) -> Self {
+        WeakSelf::new()
+    }
+}
+
+impl<T: ?Sized> PartialEq for WeakSelf<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: ?Sized> Eq for WeakSelf<T> {}
+
+impl<T: ?Sized> PartialOrd for WeakSelf<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized> Ord for WeakSelf<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<T: ?Sized> Hash for WeakSelf<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
@@ -0,0 +1,178 @@
+//! Registry of objects which remove themselves when they are dropped.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::sync::lock;
+use crate::WeakSelf;
+
+/// Registered objects, and the keys of each object by its address.
+///
+/// Each registered object has exactly one drop hook for a registry, which removes the keys listed
+/// for its address. The address stays listed while the object is alive, even if all its keys
+/// have been removed, so registering it again does not add another hook.
+struct Entries<K, T: ?Sized> {
+    objects: HashMap<K, Weak<T>>,
+    keys: HashMap<usize, Vec<K>>,
+}
+
+impl<K: Eq + Hash, T: ?Sized> Entries<K, T> {
+    /// insert an object, returning the one registered under key before and whether the new
+    /// object needs a drop hook
+    fn insert(&mut self, key: K, weak: Weak<T>) -> (Option<Weak<T>>, bool) where K: Clone {
+        let previous = self.remove(&key);
+        let keys = self.keys.entry(address(&weak));
+        let hooked = matches!(keys, Entry::Occupied(_));
+        keys.or_default().push(key.clone());
+        self.objects.insert(key, weak);
+        (previous, !hooked)
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<Weak<T>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        let weak = self.objects.remove(key)?;
+        if let Some(keys) = self.keys.get_mut(&address(&weak)) {
+            keys.retain(|registered| registered.borrow() != key);
+        }
+        Some(weak)
+    }
+
+    /// remove all keys of a dropped object
+    fn remove_object(&mut self, address: usize) {
+        for key in self.keys.remove(&address).into_iter().flatten() {
+            self.objects.remove(&key);
+        }
+    }
+}
+
+/// address identifying the object of a Weak<T> while it is alive
+fn address<T: ?Sized>(weak: &Weak<T>) -> usize {
+    weak.as_ptr() as *const () as usize
+}
+
+///Thread-safe registry of objects by key, which never keeps its objects alive.
+///
+///Objects are registered through their WeakSelf<T>, which removes the entry again when the object
+///is dropped. Clones of a WeakRegistry share the same entries.
+///
+///```rust
+/// use weak_self::WeakSelf;
+/// use weak_self::registry::WeakRegistry;
+/// use std::sync::Arc;
+/// pub struct Session {
+///     weak_self: WeakSelf<Session>,
+///     user: String,
+/// }
+///
+/// let sessions = WeakRegistry::new();
+/// let session = WeakSelf::cyclic(|weak_self| Session { weak_self, user: "alice".to_string() });
+/// sessions.register(1, &session.weak_self);
+///
+/// assert_eq!(sessions.get(&1).unwrap().user, "alice");
+/// assert_eq!(sessions.len_live(), 1);
+///
+/// drop(session);
+/// assert!(sessions.get(&1).is_none());
+/// assert_eq!(sessions.len_live(), 0);
+///```
+pub struct WeakRegistry<K, T: ?Sized> {
+    entries: Arc<Mutex<Entries<K, T>>>,
+}
+
+impl<K, T> WeakRegistry<K, T>
+    where K: Eq + Hash + Clone + Send + 'static,
+          T: ?Sized + Send + Sync + 'static
+{
+    /// Constructs a new empty WeakRegistry<K, T>
+    pub fn new() -> WeakRegistry<K, T> {
+        WeakRegistry {
+            entries: Arc::new(Mutex::new(Entries {
+                objects: HashMap::new(),
+                keys: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Register the content of weak_self under key, replacing and returning the object which was
+    /// registered under key before, if it is still alive.
+    ///
+    /// The entry is removed when the WeakSelf<T> is dropped. Registering an object again, under
+    /// the same or another key, does not add another drop hook to it. Panics if the WeakSelf<T> is
+    /// not yet initialized.
+    pub fn register(&self, key: K, weak_self: &WeakSelf<T>) -> Option<Arc<T>> {
+        let weak = weak_self.get();
+        let address = address(&weak);
+        // the previous object must not be dropped while holding the lock, as its drop hooks lock again
+        let (previous, hook) = lock(&self.entries).insert(key, weak);
+        if hook {
+            let entries = Arc::downgrade(&self.entries);
+            weak_self.on_drop(move || {
+                if let Some(entries) = entries.upgrade() {
+                    lock(&entries).remove_object(address);
+                }
+            });
+        }
+        previous.and_then(|previous| previous.upgrade())
+    }
+
+    /// Remove the entry for key, returning the object if it is still alive
+    pub fn remove<Q>(&self, key: &Q) -> Option<Arc<T>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        let previous = lock(&self.entries).remove(key);
+        previous.and_then(|previous| previous.upgrade())
+    }
+
+    /// get the object registered under key, or None if there is none or it has been dropped
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<T>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        lock(&self.entries).objects.get(key).and_then(Weak::upgrade)
+    }
+
+    /// Iterate over a snapshot of all live objects and their keys
+    pub fn iter_live(&self) -> impl Iterator<Item = (K, Arc<T>)> {
+        let live: Vec<(K, Arc<T>)> = lock(&self.entries)
+            .objects
+            .iter()
+            .filter_map(|(key, weak)| weak.upgrade().map(|content| (key.clone(), content)))
+            .collect();
+        live.into_iter()
+    }
+
+    /// number of live objects in the registry
+    pub fn len_live(&self) -> usize {
+        lock(&self.entries).objects.values().filter(|weak| weak.strong_count() > 0).count()
+    }
+}
+
+impl<K, T: ?Sized> Clone for WeakRegistry<K, T> {
+    fn clone(&self) -> Self {
+        WeakRegistry {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<K, T> Default for WeakRegistry<K, T>
+    where K: Eq + Hash + Clone + Send + 'static,
+          T: ?Sized + Send + Sync + 'static
+{
+    fn default() -> Self {
+        WeakRegistry::new()
+    }
+}
+
+impl<K: fmt::Debug, T: ?Sized> fmt::Debug for WeakRegistry<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(lock(&self.entries).objects.keys()).finish()
+    }
+}
@@ -0,0 +1,119 @@
+//! Serde support, enabled with the `serde` feature.
+//!
+//! A WeakSelf<T> serializes as none and deserializes to an empty WeakSelf<T>, also when its field
+//! is missing from the input, so self-describing formats accept data written without the field.
+//! The single-threaded [`rc::WeakSelf`](crate::rc::WeakSelf) behaves the same. Use
+//! [`deserialize_arc`] to deserialize a value straight into an Arc<T> with its WeakSelf<T>
+//! linked, or [`deserialize_rc`] for an Rc<T>:
+//!
+//!```rust
+//! use serde::{Deserialize, Serialize};
+//! use weak_self::{HasWeakSelf, WeakSelf};
+//! use std::sync::Arc;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! pub struct Foo {
+//!     weak_self: WeakSelf<Foo>,
+//!     name: String,
+//! }
+//!
+//! impl HasWeakSelf for Foo {
+//!     fn weak_self(&self) -> &WeakSelf<Self> {
+//!         &self.weak_self
+//!     }
+//! }
+//!
+//! let foo = WeakSelf::cyclic(|weak_self| Foo { weak_self, name: "foo".to_string() });
+//! let json = serde_json::to_string(&*foo).unwrap();
+//! assert_eq!(json, r#"{"weak_self":null,"name":"foo"}"#);
+//!
+//! let mut deserializer = serde_json::Deserializer::from_str(&json);
+//! let copy: Arc<Foo> = weak_self::serde::deserialize_arc(&mut deserializer).unwrap();
+//! assert_eq!(copy.name, "foo");
+//! assert!(Arc::ptr_eq(&copy, &copy.shared_from_this()));
+//!
+//! let copy: Foo = serde_json::from_str(r#"{"name":"bar"}"#).unwrap();
+//! assert_eq!(copy.name, "bar");
+//!```
+
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::fmt;
+use core::marker::PhantomData;
+
+use ::serde::de::{self, Deserialize, Deserializer, Visitor};
+use ::serde::ser::{Serialize, Serializer};
+
+use crate::ptr::{SharedPtr, StdRc};
+use crate::{rc, HasWeakSelf, WeakSelf};
+
+impl<T: ?Sized, P: SharedPtr<T>> Serialize for WeakSelf<T, P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_none()
+    }
+}
+
+impl<'de, T: ?Sized, P: SharedPtr<T>> Deserialize<'de> for WeakSelf<T, P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // a missing field is deserialized as none
+        deserializer.deserialize_option(EmptyVisitor(PhantomData))
+    }
+}
+
+struct EmptyVisitor<T: ?Sized, P: SharedPtr<T>>(PhantomData<fn() -> WeakSelf<T, P>>);
+
+impl<'de, T: ?Sized, P: SharedPtr<T>> Visitor<'de> for EmptyVisitor<T, P> {
+    type Value = WeakSelf<T, P>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "none or unit")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(WeakSelf::default())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_unit(self)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(WeakSelf::default())
+    }
+}
+
+impl<T: ?Sized> Serialize for rc::WeakSelf<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'de, T: ?Sized> Deserialize<'de> for rc::WeakSelf<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        WeakSelf::<T, StdRc>::deserialize(deserializer).map(|_| rc::WeakSelf::new())
+    }
+}
+
+/// Deserialize a value into a new Arc<T> and initialize its WeakSelf<T>.
+///
+/// Can also be used with `#[serde(deserialize_with = "weak_self::serde::deserialize_arc")]`.
+pub fn deserialize_arc<'de, D, T>(deserializer: D) -> Result<Arc<T>, D::Error>
+    where D: Deserializer<'de>,
+          T: Deserialize<'de> + HasWeakSelf
+{
+    let content = Arc::new(T::deserialize(deserializer)?);
+    content.weak_self().try_init(&content).map_err(de::Error::custom)?;
+    Ok(content)
+}
+
+/// Deserialize a value into a new Rc<T> and initialize its [`rc::WeakSelf<T>`].
+///
+/// Can also be used with `#[serde(deserialize_with = "weak_self::serde::deserialize_rc")]`.
+pub fn deserialize_rc<'de, D, T>(deserializer: D) -> Result<Rc<T>, D::Error>
+    where D: Deserializer<'de>,
+          T: Deserialize<'de> + rc::HasWeakSelf
+{
+    let content = Rc::new(T::deserialize(deserializer)?);
+    content.weak_self().try_init(&content).map_err(de::Error::custom)?;
+    Ok(content)
+}
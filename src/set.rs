@@ -0,0 +1,207 @@
+//! Sets of objects which do not own their members.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Weak};
+
+/// Minimum number of entries before dead entries are purged
+const MIN_PURGE_THRESHOLD: usize = 8;
+
+///Weak<T> pointer which compares, orders and hashes by the identity of its allocation.
+///
+///Two WeakKey<T> are equal if they point to the same allocation, regardless of whether it is
+///still alive. As a Weak<T> keeps its allocation from being reused, the identity stays unique
+///for the lifetime of the WeakKey<T>.
+#[repr(transparent)]
+pub struct WeakKey<T: ?Sized>(Weak<T>);
+
+impl<T: ?Sized> WeakKey<T> {
+    /// Constructs a new WeakKey<T> from a Weak<T> pointer
+    pub fn new(weak: Weak<T>) -> WeakKey<T> {
+        WeakKey(weak)
+    }
+
+    /// get the Weak<T> pointer of this key
+    pub fn weak(&self) -> &Weak<T> {
+        &self.0
+    }
+
+    /// get the Weak<T> pointer of this key
+    pub fn into_weak(self) -> Weak<T> {
+        self.0
+    }
+
+    /// get Some Arc<T> pointer to the content, or None if already dropped
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        self.0.upgrade()
+    }
+
+    /// true if the content has not been dropped yet
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+
+    /// borrow a Weak<T> pointer as a WeakKey<T>, to look it up without cloning it
+    fn from_ref(weak: &Weak<T>) -> &WeakKey<T> {
+        // SAFETY: WeakKey<T> is a transparent wrapper of Weak<T>
+        unsafe { &*(weak as *const Weak<T> as *const WeakKey<T>) }
+    }
+
+    fn addr(&self) -> *const () {
+        self.0.as_ptr() as *const ()
+    }
+}
+
+impl<T: ?Sized> From<Weak<T>> for WeakKey<T> {
+    fn from(weak: Weak<T>) -> Self {
+        WeakKey(weak)
+    }
+}
+
+impl<T: ?Sized> From<&Arc<T>> for WeakKey<T> {
+    fn from(content: &Arc<T>) -> Self {
+        WeakKey(Arc::downgrade(content))
+    }
+}
+
+impl<T: ?Sized> Clone for WeakKey<T> {
+    fn clone(&self) -> Self {
+        WeakKey(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> PartialEq for WeakKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+impl<T: ?Sized> Eq for WeakKey<T> {}
+
+impl<T: ?Sized> PartialOrd for WeakKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized> Ord for WeakKey<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.addr().cmp(&other.addr())
+    }
+}
+
+impl<T: ?Sized> Hash for WeakKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for WeakKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WeakKey({:p})", self.addr())
+    }
+}
+
+///Set of objects held by Weak<T> pointers, for example observers registered with the Weak<T>
+///pointer from their WeakSelf<T>.
+///
+///All methods ignore objects which have been dropped. Their entries are purged lazily once the
+///set has grown to twice the size it had after the last purge, so purging is amortized over
+///insertions.
+///
+///```rust
+/// use weak_self::WeakSelf;
+/// use weak_self::set::WeakSet;
+/// pub struct Observer {
+///     weak_self: WeakSelf<Observer>
+/// }
+///
+/// let mut observers = WeakSet::new();
+/// let first = WeakSelf::cyclic(|weak_self| Observer { weak_self });
+/// let second = WeakSelf::cyclic(|weak_self| Observer { weak_self });
+/// assert!(observers.insert(first.weak_self.get()));
+/// assert!(observers.insert(second.weak_self.get()));
+/// assert!(!observers.insert(first.weak_self.get()));
+///
+/// drop(first);
+/// assert_eq!(observers.upgrade_all().len(), 1);
+/// assert!(observers.contains(&second.weak_self.get()));
+///```
+pub struct WeakSet<T: ?Sized> {
+    entries: HashSet<WeakKey<T>>,
+    purge_threshold: usize,
+}
+
+impl<T: ?Sized> WeakSet<T> {
+    /// Constructs a new empty WeakSet<T>
+    pub fn new() -> WeakSet<T> {
+        WeakSet {
+            entries: HashSet::new(),
+            purge_threshold: MIN_PURGE_THRESHOLD,
+        }
+    }
+
+    /// Add an object to the set, returning false if it was already a live member or has been dropped
+    pub fn insert(&mut self, weak: Weak<T>) -> bool {
+        if self.entries.len() >= self.purge_threshold {
+            self.purge();
+        }
+        let key = WeakKey(weak);
+        // an entry for the same allocation is dead exactly if the new one is
+        key.is_alive() && self.entries.insert(key)
+    }
+
+    /// Remove an object from the set, returning true if it was a live member
+    pub fn remove(&mut self, weak: &Weak<T>) -> bool {
+        self.entries.take(WeakKey::from_ref(weak)).is_some_and(|previous| previous.is_alive())
+    }
+
+    /// true if the object is a live member of the set
+    pub fn contains(&self, weak: &Weak<T>) -> bool {
+        self.entries.get(WeakKey::from_ref(weak)).is_some_and(WeakKey::is_alive)
+    }
+
+    /// get Arc<T> pointers to all live members
+    pub fn upgrade_all(&self) -> Vec<Arc<T>> {
+        self.entries.iter().filter_map(WeakKey::upgrade).collect()
+    }
+
+    /// number of live members
+    pub fn len_live(&self) -> usize {
+        self.entries.iter().filter(|key| key.is_alive()).count()
+    }
+
+    /// number of entries, including those of dropped objects which have not been purged yet
+    pub fn len_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Remove the entries of all dropped objects
+    pub fn purge(&mut self) {
+        self.entries.retain(WeakKey::is_alive);
+        self.purge_threshold = (self.entries.len() * 2).max(MIN_PURGE_THRESHOLD);
+    }
+}
+
+impl<T: ?Sized> Clone for WeakSet<T> {
+    fn clone(&self) -> Self {
+        WeakSet {
+            entries: self.entries.clone(),
+            purge_threshold: self.purge_threshold,
+        }
+    }
+}
+
+impl<T: ?Sized> Default for WeakSet<T> {
+    fn default() -> Self {
+        WeakSet::new()
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for WeakSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.entries.iter().filter(|key| key.is_alive())).finish()
+    }
+}
@@ -0,0 +1,97 @@
+//! Cells and hook lists a WeakSelf stores its state in, chosen by [`Storage`](crate::ptr::Storage).
+//!
+//! The traits are public so they can bound the associated types of `Storage`, but this module is
+//! private, so no other crate can implement or call them.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::mem;
+
+use crate::hooks::{self, DropHooks};
+use crate::once::OnceCell;
+
+/// keeps other crates from implementing Storage
+pub trait Sealed {}
+
+/// A cell which can be written to only once
+pub trait SetOnce<V> {
+    fn new() -> Self;
+
+    fn with_value(value: V) -> Self;
+
+    fn get(&self) -> Option<&V>;
+
+    /// set the value, or give it back if the cell has already been set
+    fn set(&self, value: V) -> Result<(), V>;
+}
+
+/// A list of hooks which run once when the list is dropped, filled through
+/// [`DropHook`](crate::ptr::DropHook)
+pub trait HookList {
+    fn new() -> Self;
+}
+
+impl<V> SetOnce<V> for OnceCell<V> {
+    fn new() -> Self {
+        OnceCell::new()
+    }
+
+    fn with_value(value: V) -> Self {
+        OnceCell::with_value(value)
+    }
+
+    fn get(&self) -> Option<&V> {
+        OnceCell::get(self)
+    }
+
+    fn set(&self, value: V) -> Result<(), V> {
+        OnceCell::set(self, value)
+    }
+}
+
+impl HookList for DropHooks {
+    fn new() -> Self {
+        DropHooks::new()
+    }
+}
+
+impl<V> SetOnce<V> for core::cell::OnceCell<V> {
+    fn new() -> Self {
+        core::cell::OnceCell::new()
+    }
+
+    fn with_value(value: V) -> Self {
+        core::cell::OnceCell::from(value)
+    }
+
+    fn get(&self) -> Option<&V> {
+        core::cell::OnceCell::get(self)
+    }
+
+    fn set(&self, value: V) -> Result<(), V> {
+        core::cell::OnceCell::set(self, value)
+    }
+}
+
+/// Hook list of single-threaded WeakSelf kinds, which need no atomics, and whose hooks need not
+/// be Send as they run on the owning thread
+pub struct LocalHooks(RefCell<Vec<Box<dyn FnOnce()>>>);
+
+impl LocalHooks {
+    pub(crate) fn push(&self, hook: Box<dyn FnOnce()>) {
+        self.0.borrow_mut().push(hook);
+    }
+}
+
+impl HookList for LocalHooks {
+    fn new() -> Self {
+        LocalHooks(RefCell::new(Vec::new()))
+    }
+}
+
+impl Drop for LocalHooks {
+    fn drop(&mut self) {
+        hooks::run(mem::take(self.0.get_mut()));
+    }
+}
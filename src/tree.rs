@@ -0,0 +1,250 @@
+//! Tree of nodes holding strong pointers to their children and weak pointers to their parent.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::iter;
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+use crate::sync::lock;
+use crate::{HasWeakSelf, WeakSelf};
+
+///Node of a thread-safe tree, using its WeakSelf for the back-links of its children.
+///
+///A node owns its children through Arc pointers and points to its parent through a Weak pointer,
+///so dropping the root frees the whole tree.
+///
+///Structural changes only lock the nodes they touch, so changes of different trees run in
+///parallel. Appending a child locks the parent links of the new parent, its ancestors and the
+///child, which keeps the child from becoming an ancestor of its new parent meanwhile.
+///
+///```rust
+/// use weak_self::tree::TreeNode;
+/// use std::sync::Arc;
+///
+/// let root = TreeNode::new("root");
+/// let a = TreeNode::new("a");
+/// let b = TreeNode::new("b");
+/// root.append_child(a.clone());
+/// root.append_child(b.clone());
+/// a.append_child(TreeNode::new("a1"));
+///
+/// let names: Vec<&str> = root.depth_first().map(|node| *node.value()).collect();
+/// assert_eq!(names, ["root", "a", "a1", "b"]);
+/// let names: Vec<&str> = root.breadth_first().map(|node| *node.value()).collect();
+/// assert_eq!(names, ["root", "a", "b", "a1"]);
+///
+/// // reparenting updates the back-links
+/// b.append_child(a.clone());
+/// assert!(Arc::ptr_eq(&a.parent().unwrap(), &b));
+/// assert_eq!(root.children().len(), 1);
+///
+/// let a1 = a.children()[0].clone();
+/// let names: Vec<&str> = a1.ancestors().map(|node| *node.value()).collect();
+/// assert_eq!(names, ["a", "b", "root"]);
+///
+/// a.detach();
+/// assert!(a.parent().is_none());
+/// assert!(b.children().is_empty());
+///```
+pub struct TreeNode<T> {
+    weak_self: WeakSelf<TreeNode<T>>,
+    parent: Mutex<Weak<TreeNode<T>>>,
+    children: Mutex<Vec<Arc<TreeNode<T>>>>,
+    value: T,
+}
+
+impl<T> TreeNode<T> {
+    /// Constructs a new node without parent and children
+    pub fn new(value: T) -> Arc<TreeNode<T>> {
+        WeakSelf::cyclic(|weak_self| TreeNode {
+            weak_self,
+            parent: Mutex::new(Weak::new()),
+            children: Mutex::new(Vec::new()),
+            value,
+        })
+    }
+
+    /// get the value of this node
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// get the parent of this node, or None if this node is a root
+    pub fn parent(&self) -> Option<Arc<TreeNode<T>>> {
+        lock(&self.parent).upgrade()
+    }
+
+    /// get a snapshot of the children of this node
+    pub fn children(&self) -> Vec<Arc<TreeNode<T>>> {
+        lock(&self.children).clone()
+    }
+
+    /// Append child as the last child of this node, detaching it from its previous parent.
+    ///
+    /// Panics if child is this node or one of its ancestors, as that would create a cycle.
+    pub fn append_child(&self, child: Arc<TreeNode<T>>) {
+        let this = self.weak_self.arc();
+        loop {
+            // this node and its ancestors, whose parent links must not change while linking
+            let path: Vec<Arc<TreeNode<T>>> = iter::once(this.clone()).chain(this.ancestors()).collect();
+            if path.iter().any(|node| Arc::ptr_eq(node, &child)) {
+                panic!("cannot append a TreeNode to itself or to one of its descendants");
+            }
+            let mut links = ParentLinks::lock(path.iter().map(|node| &**node).chain([&*child]));
+            // the path may have changed before it was locked
+            let unchanged = path.windows(2).all(|pair| links.get(&pair[0]).as_ptr() == Arc::as_ptr(&pair[1]))
+                && links.get(&path[path.len() - 1]).strong_count() == 0;
+            if !unchanged {
+                continue;
+            }
+            let previous = mem::replace(links.get(&child), Arc::downgrade(&this)).upgrade();
+            if let Some(previous) = &previous {
+                previous.remove_child(&child);
+            }
+            lock(&self.children).push(child.clone());
+            // the previous parent must not be dropped while holding the links, as its drop may change trees
+            drop(links);
+            return;
+        }
+    }
+
+    /// Remove this node from the children of its parent, making it a root
+    pub fn detach(&self) {
+        let mut links = ParentLinks::lock([self]);
+        let parent = mem::take(links.get(self)).upgrade();
+        let removed = parent.as_ref().and_then(|parent| parent.remove_child(self));
+        // neither the parent nor this node must be dropped while holding the link
+        drop(links);
+        drop(removed);
+    }
+
+    /// remove child from the children of this node, the caller holds the parent link of child
+    fn remove_child(&self, child: &TreeNode<T>) -> Option<Arc<TreeNode<T>>> {
+        let mut children = lock(&self.children);
+        let index = children.iter().position(|node| std::ptr::eq(Arc::as_ptr(node), child))?;
+        Some(children.remove(index))
+    }
+
+    /// Iterate over the ancestors of this node, starting with its parent
+    pub fn ancestors(&self) -> Ancestors<T> {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// Iterate depth-first over this node and its descendants, visiting each node before its children
+    pub fn depth_first(&self) -> DepthFirst<T> {
+        DepthFirst {
+            stack: vec![self.weak_self.arc()],
+        }
+    }
+
+    /// Iterate breadth-first over this node and its descendants
+    pub fn breadth_first(&self) -> BreadthFirst<T> {
+        BreadthFirst {
+            queue: VecDeque::from([self.weak_self.arc()]),
+        }
+    }
+}
+
+impl<T> HasWeakSelf for TreeNode<T> {
+    fn weak_self(&self) -> &WeakSelf<Self> {
+        &self.weak_self
+    }
+}
+
+impl<T> Drop for TreeNode<T> {
+    fn drop(&mut self) {
+        // drop the descendants iteratively, so deep trees do not overflow the stack
+        let mut descendants = mem::take(self.children.get_mut().unwrap_or_else(PoisonError::into_inner));
+        while let Some(child) = descendants.pop() {
+            if let Some(mut child) = Arc::into_inner(child) {
+                descendants.append(child.children.get_mut().unwrap_or_else(PoisonError::into_inner));
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TreeNode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TreeNode")
+            .field("value", &self.value)
+            .field("children", &self.children())
+            .finish()
+    }
+}
+
+/// Locked parent links of some nodes.
+///
+/// The links are locked in address order, so concurrent changes do not deadlock. Children are
+/// only locked while holding the parent links of the nodes being moved, and never the other way
+/// around.
+struct ParentLinks<'a, T> {
+    guards: Vec<(&'a TreeNode<T>, ParentLink<'a, T>)>,
+}
+
+type ParentLink<'a, T> = MutexGuard<'a, Weak<TreeNode<T>>>;
+
+impl<'a, T> ParentLinks<'a, T> {
+    fn lock(nodes: impl IntoIterator<Item = &'a TreeNode<T>>) -> ParentLinks<'a, T> {
+        let mut nodes: Vec<&TreeNode<T>> = nodes.into_iter().collect();
+        nodes.sort_by_key(|node| *node as *const TreeNode<T>);
+        nodes.dedup_by(|a, b| std::ptr::eq(*a, *b));
+        ParentLinks {
+            guards: nodes.into_iter().map(|node| (node, lock(&node.parent))).collect(),
+        }
+    }
+
+    fn get(&mut self, node: &TreeNode<T>) -> &mut Weak<TreeNode<T>> {
+        let (_, guard) = self.guards.iter_mut()
+            .find(|(locked, _)| std::ptr::eq(*locked, node))
+            .expect("parent link is locked");
+        guard
+    }
+}
+
+/// Iterator returned by [`TreeNode::ancestors`]
+pub struct Ancestors<T> {
+    next: Option<Arc<TreeNode<T>>>,
+}
+
+impl<T> Iterator for Ancestors<T> {
+    type Item = Arc<TreeNode<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next.take()?;
+        self.next = node.parent();
+        Some(node)
+    }
+}
+
+/// Iterator returned by [`TreeNode::depth_first`]
+pub struct DepthFirst<T> {
+    stack: Vec<Arc<TreeNode<T>>>,
+}
+
+impl<T> Iterator for DepthFirst<T> {
+    type Item = Arc<TreeNode<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(lock(&node.children).iter().rev().cloned());
+        Some(node)
+    }
+}
+
+/// Iterator returned by [`TreeNode::breadth_first`]
+pub struct BreadthFirst<T> {
+    queue: VecDeque<Arc<TreeNode<T>>>,
+}
+
+impl<T> Iterator for BreadthFirst<T> {
+    type Item = Arc<TreeNode<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(lock(&node.children).iter().cloned());
+        Some(node)
+    }
+}
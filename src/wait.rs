@@ -0,0 +1,149 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::ptr::{Atomic, SharedPtr};
+use crate::sync::lock;
+use crate::WeakSelf;
+
+/// Wakers of futures waiting for a cell, by key of the cell.
+///
+/// Blocking waiters park on CONDVAR with this lock. All cells share the lock and the condvar, as
+/// waiting is rare and only lasts until a cell is initialized.
+static PARKING: Mutex<Vec<(usize, Waker)>> = Mutex::new(Vec::new());
+static CONDVAR: Condvar = Condvar::new();
+
+/// Wake up all threads and futures waiting for the cell with the given key.
+///
+/// Waiters flag the cell while holding the lock, so taking the lock here ensures they are either
+/// parked already or will see the value.
+pub(crate) fn notify(key: usize) {
+    let mut wakers = Vec::new();
+    {
+        let mut parking = lock(&PARKING);
+        parking.retain(|(waiting, waker)| {
+            if *waiting == key {
+                wakers.push(waker.clone());
+            }
+            *waiting != key
+        });
+        CONDVAR.notify_all();
+    }
+    for waker in wakers {
+        waker.wake();
+    }
+}
+
+/// Waiting for the initialization of a WeakSelf<T> from other threads, enabled with the `std`
+/// feature. Only thread-safe pointer kinds support it, a single-threaded WeakSelf could only be
+/// initialized by the waiting thread itself.
+impl<T: ?Sized, P: SharedPtr<T, Storage = Atomic>> WeakSelf<T, P> {
+    /// get the Weak<T> pointer to the content, blocking until the WeakSelf<T> is initialized
+    ///
+    ///```rust
+    /// use weak_self::WeakSelf;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// pub struct Foo {
+    ///     weak_self: WeakSelf<Foo>
+    /// }
+    ///
+    /// let foo = Arc::new(Foo { weak_self: WeakSelf::new() });
+    /// thread::scope(|scope| {
+    ///     let waiter = scope.spawn(|| foo.weak_self.wait().upgrade().is_some());
+    ///     foo.weak_self.init(&foo);
+    ///     assert!(waiter.join().unwrap());
+    /// });
+    ///```
+    pub fn wait(&self) -> &P::Weak {
+        if let Some(weak) = self.try_get() {
+            return weak;
+        }
+        let mut parking = lock(&PARKING);
+        loop {
+            if self.cell.register_waiter() {
+                return self.try_get().expect("WeakSelf is initialized");
+            }
+            parking = CONDVAR.wait(parking).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// get the Weak<T> pointer to the content, blocking until the WeakSelf<T> is initialized or
+    /// the timeout elapses
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<&P::Weak> {
+        if let Some(weak) = self.try_get() {
+            return Some(weak);
+        }
+        let deadline = Instant::now().checked_add(timeout);
+        let mut parking = lock(&PARKING);
+        loop {
+            if self.cell.register_waiter() {
+                return self.try_get();
+            }
+            let remaining = match deadline {
+                Some(deadline) => deadline.checked_duration_since(Instant::now())?,
+                None => Duration::MAX,
+            };
+            parking = CONDVAR.wait_timeout(parking, remaining).unwrap_or_else(PoisonError::into_inner).0;
+        }
+    }
+
+    /// Create a future which resolves to the Weak<T> pointer to the content once the WeakSelf<T>
+    /// is initialized
+    pub fn initialized(&self) -> Initialized<'_, T, P> {
+        Initialized {
+            weak_self: self,
+            waker: None,
+        }
+    }
+}
+
+/// Future returned by [`WeakSelf::initialized`]
+#[must_use = "futures do nothing unless polled"]
+pub struct Initialized<'a, T: ?Sized, P: SharedPtr<T, Storage = Atomic>> {
+    weak_self: &'a WeakSelf<T, P>,
+    /// waker registered in PARKING, if any
+    waker: Option<Waker>,
+}
+
+impl<'a, T: ?Sized, P: SharedPtr<T, Storage = Atomic>> Initialized<'a, T, P> {
+    fn unregister(&mut self, parking: &mut Vec<(usize, Waker)>) {
+        if let Some(waker) = self.waker.take() {
+            let key = self.weak_self.cell.key();
+            let registered = parking.iter()
+                .position(|(waiting, registered)| *waiting == key && registered.will_wake(&waker));
+            if let Some(index) = registered {
+                parking.swap_remove(index);
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized, P: SharedPtr<T, Storage = Atomic>> Future for Initialized<'a, T, P> {
+    type Output = &'a P::Weak;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'a P::Weak> {
+        let this = self.get_mut();
+        if let Some(weak) = this.weak_self.try_get() {
+            return Poll::Ready(weak);
+        }
+        let mut parking = lock(&PARKING);
+        this.unregister(&mut parking);
+        if this.weak_self.cell.register_waiter() {
+            return Poll::Ready(this.weak_self.try_get().expect("WeakSelf is initialized"));
+        }
+        parking.push((this.weak_self.cell.key(), cx.waker().clone()));
+        this.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<'a, T: ?Sized, P: SharedPtr<T, Storage = Atomic>> Drop for Initialized<'a, T, P> {
+    fn drop(&mut self) {
+        if self.waker.is_some() {
+            self.unregister(&mut lock(&PARKING));
+        }
+    }
+}
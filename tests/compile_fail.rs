@@ -0,0 +1,8 @@
+//! Checks that WeakSelf only crosses threads when its content and its drop hooks may, and that
+//! single-threaded WeakSelf kinds cannot be waited for.
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
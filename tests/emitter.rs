@@ -0,0 +1,152 @@
+//! Checks the re-entrancy rules of Emitter.
+
+use std::sync::{Arc, Mutex};
+
+use weak_self::emitter::{Emitter, Subscription};
+use weak_self::WeakSelf;
+
+/// subscriber logging the events it receives
+struct Subscriber {
+    weak_self: WeakSelf<Subscriber>,
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+    subscription: Mutex<Option<Subscription>>,
+}
+
+impl Subscriber {
+    fn new(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Arc<Subscriber> {
+        WeakSelf::cyclic(|weak_self| Subscriber {
+            weak_self,
+            name,
+            log: log.clone(),
+            subscription: Mutex::new(None),
+        })
+    }
+
+    fn record(&self, event: &str) {
+        self.log.lock().unwrap().push(format!("{}:{}", self.name, event));
+    }
+
+    /// subscribe, keeping the subscription in the subscriber
+    fn subscribe<F>(&self, emitter: &Emitter<str>, f: F)
+        where F: Fn(Arc<Subscriber>, &str) + Send + Sync + 'static
+    {
+        *self.subscription.lock().unwrap() = Some(emitter.subscribe(&self.weak_self, f));
+    }
+
+    fn unsubscribe(&self) {
+        self.subscription.lock().unwrap().take();
+    }
+}
+
+fn log() -> Arc<Mutex<Vec<String>>> {
+    Arc::new(Mutex::new(Vec::new()))
+}
+
+fn take(log: &Mutex<Vec<String>>) -> Vec<String> {
+    std::mem::take(&mut *log.lock().unwrap())
+}
+
+#[test]
+fn subscribers_added_during_emit_receive_later_events() {
+    let log = log();
+    let emitter = Emitter::new();
+    let late = Subscriber::new("late", &log);
+    let early = Subscriber::new("early", &log);
+    early.subscribe(&emitter, {
+        let emitter = emitter.clone();
+        let late = late.clone();
+        move |this, event| {
+            this.record(event);
+            if late.subscription.lock().unwrap().is_none() {
+                late.subscribe(&emitter, |this, event| this.record(event));
+            }
+        }
+    });
+
+    assert_eq!(emitter.emit("first"), 1);
+    assert_eq!(take(&log), ["early:first"]);
+    assert_eq!(emitter.emit("second"), 2);
+    assert_eq!(take(&log), ["early:second", "late:second"]);
+    // the callback holds a clone of the emitter
+    early.unsubscribe();
+}
+
+#[test]
+fn subscriber_may_unsubscribe_itself_during_emit() {
+    let log = log();
+    let emitter = Emitter::new();
+    let once = Subscriber::new("once", &log);
+    once.subscribe(&emitter, |this, event| {
+        this.record(event);
+        this.unsubscribe();
+    });
+    let always = Subscriber::new("always", &log);
+    always.subscribe(&emitter, |this, event| this.record(event));
+
+    assert_eq!(emitter.emit("first"), 2);
+    assert_eq!(emitter.emit("second"), 1);
+    assert_eq!(take(&log), ["once:first", "always:first", "always:second"]);
+}
+
+#[test]
+fn subscriber_unsubscribed_during_emit_is_not_called_by_it() {
+    let log = log();
+    let emitter = Emitter::new();
+    let second = Subscriber::new("second", &log);
+    let first = Subscriber::new("first", &log);
+    first.subscribe(&emitter, {
+        let second = second.clone();
+        move |this, event| {
+            this.record(event);
+            second.unsubscribe();
+        }
+    });
+    second.subscribe(&emitter, |this, event| this.record(event));
+
+    assert_eq!(emitter.emit("event"), 1);
+    assert_eq!(take(&log), ["first:event"]);
+    assert_eq!(format!("{:?}", emitter), "Emitter { subscribers: 1 }");
+}
+
+#[test]
+fn nested_emit_is_delivered_before_the_outer_emit_continues() {
+    let log = log();
+    let emitter = Emitter::new();
+    let first = Subscriber::new("first", &log);
+    first.subscribe(&emitter, {
+        let emitter = emitter.clone();
+        move |this, event| {
+            this.record(event);
+            if event == "outer" {
+                assert_eq!(emitter.emit("inner"), 2);
+            }
+        }
+    });
+    let second = Subscriber::new("second", &log);
+    second.subscribe(&emitter, |this, event| this.record(event));
+
+    assert_eq!(emitter.emit("outer"), 2);
+    assert_eq!(take(&log), ["first:outer", "first:inner", "second:inner", "second:outer"]);
+    first.unsubscribe();
+}
+
+#[test]
+fn dropped_subscribers_are_removed_lazily() {
+    let log = log();
+    let emitter = Emitter::new();
+    let kept = Subscriber::new("kept", &log);
+    kept.subscribe(&emitter, |this, event| this.record(event));
+    let dropped = Subscriber::new("dropped", &log);
+    emitter.subscribe(&dropped.weak_self, |this, event| this.record(event)).detach();
+
+    drop(dropped);
+    assert_eq!(format!("{:?}", emitter), "Emitter { subscribers: 2 }");
+    assert_eq!(emitter.emit("event"), 1);
+    assert_eq!(format!("{:?}", emitter), "Emitter { subscribers: 1 }");
+    assert_eq!(take(&log), ["kept:event"]);
+
+    kept.unsubscribe();
+    assert_eq!(emitter.emit("event"), 0);
+    assert_eq!(format!("{:?}", emitter), "Emitter { subscribers: 0 }");
+}
@@ -0,0 +1,280 @@
+//! Model tests checking all interleavings of concurrent WeakSelf operations.
+//!
+//! RUSTFLAGS="--cfg loom" cargo test --features loom --test loom --release
+
+#![cfg(loom)]
+
+use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use loom::thread;
+use weak_self::{InitError, WeakSelf};
+
+#[test]
+fn init_races_try_get() {
+    loom::model(|| {
+        let weak_self = loom::sync::Arc::new(WeakSelf::<usize>::new());
+
+        let initializer = thread::spawn({
+            let weak_self = weak_self.clone();
+            move || {
+                let content = std::sync::Arc::new(42);
+                weak_self.init(&content);
+                content
+            }
+        });
+
+        if let Some(weak) = weak_self.try_get() {
+            // the content is kept alive by the initializer until it is joined
+            assert_eq!(weak.upgrade().map(|content| *content), Some(42));
+        }
+
+        let content = initializer.join().unwrap();
+        assert!(std::sync::Arc::ptr_eq(&content, &weak_self.get().upgrade().unwrap()));
+    });
+}
+
+#[test]
+fn repeated_init_has_one_winner() {
+    loom::model(|| {
+        let weak_self = loom::sync::Arc::new(WeakSelf::<usize>::new());
+
+        let threads: Vec<_> = (0..2)
+            .map(|value| {
+                let weak_self = weak_self.clone();
+                thread::spawn(move || {
+                    let content = std::sync::Arc::new(value);
+                    let result = weak_self.try_init(&content);
+                    if result.is_err() {
+                        // losers only return once the winner's pointer is visible
+                        assert!(weak_self.try_get().is_some());
+                    }
+                    (content, result)
+                })
+            })
+            .collect();
+        let results: Vec<_> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+
+        let winners: Vec<_> = results.iter().filter(|(_, result)| result.is_ok()).collect();
+        assert_eq!(winners.len(), 1);
+        assert!(results.iter().any(|(_, result)| *result == Err(InitError::AlreadyInitialized)));
+        assert!(std::sync::Arc::ptr_eq(&winners[0].0, &weak_self.get().upgrade().unwrap()));
+    });
+}
+
+/// Reference counted pointer built on loom atomics, so loom sees the reference counts racing.
+///
+/// std::sync::Arc counts with std atomics, which loom can not interleave.
+mod loom_arc {
+    use std::mem::ManuallyDrop;
+    use std::ops::Deref;
+    use std::ptr::{self, NonNull};
+
+    use loom::sync::atomic::{fence, AtomicUsize, Ordering};
+    use weak_self::ptr::SharedPtr;
+
+    /// SharedPtr kind of Strong<T> and Weak<T>
+    pub struct LoomArc;
+
+    #[repr(C)]
+    struct Inner<T> {
+        /// first, so a pointer to Inner<T> also points to the value
+        value: ManuallyDrop<T>,
+        strong: AtomicUsize,
+        /// all strong pointers together hold one weak reference, like with std::sync::Arc
+        weak: AtomicUsize,
+    }
+
+    pub struct Strong<T>(NonNull<Inner<T>>);
+    pub struct Weak<T>(NonNull<Inner<T>>);
+
+    unsafe impl<T: Send + Sync> Send for Strong<T> {}
+    unsafe impl<T: Send + Sync> Sync for Strong<T> {}
+    unsafe impl<T: Send + Sync> Send for Weak<T> {}
+    unsafe impl<T: Send + Sync> Sync for Weak<T> {}
+
+    impl<T> Strong<T> {
+        pub fn new(value: T) -> Strong<T> {
+            let inner = Box::new(Inner {
+                value: ManuallyDrop::new(value),
+                strong: AtomicUsize::new(1),
+                weak: AtomicUsize::new(1),
+            });
+            Strong(NonNull::from(Box::leak(inner)))
+        }
+
+        fn inner(&self) -> &Inner<T> {
+            // SAFETY: a strong pointer keeps the allocation and the value alive
+            unsafe { self.0.as_ref() }
+        }
+    }
+
+    impl<T> Weak<T> {
+        fn inner(&self) -> &Inner<T> {
+            // SAFETY: a weak pointer keeps the allocation alive, the value is not touched
+            unsafe { self.0.as_ref() }
+        }
+    }
+
+    impl<T> Deref for Strong<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            &self.inner().value
+        }
+    }
+
+    impl<T> Clone for Strong<T> {
+        fn clone(&self) -> Self {
+            self.inner().strong.fetch_add(1, Ordering::Relaxed);
+            Strong(self.0)
+        }
+    }
+
+    impl<T> Clone for Weak<T> {
+        fn clone(&self) -> Self {
+            self.inner().weak.fetch_add(1, Ordering::Relaxed);
+            Weak(self.0)
+        }
+    }
+
+    impl<T> Drop for Strong<T> {
+        fn drop(&mut self) {
+            if self.inner().strong.fetch_sub(1, Ordering::Release) == 1 {
+                fence(Ordering::Acquire);
+                // SAFETY: this was the last strong pointer, nobody can upgrade any more
+                unsafe { ManuallyDrop::drop(&mut (*self.0.as_ptr()).value) };
+                drop(Weak(self.0));
+            }
+        }
+    }
+
+    impl<T> Drop for Weak<T> {
+        fn drop(&mut self) {
+            if self.inner().weak.fetch_sub(1, Ordering::Release) == 1 {
+                fence(Ordering::Acquire);
+                // SAFETY: this was the last pointer of any kind
+                drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+            }
+        }
+    }
+
+    impl<T> SharedPtr<T> for LoomArc {
+        type Strong = Strong<T>;
+        type Weak = Weak<T>;
+        type Storage = weak_self::ptr::Atomic;
+
+        fn downgrade(this: &Strong<T>) -> Weak<T> {
+            this.inner().weak.fetch_add(1, Ordering::Relaxed);
+            Weak(this.0)
+        }
+
+        fn upgrade(weak: &Weak<T>) -> Option<Strong<T>> {
+            let strong = &weak.inner().strong;
+            let mut count = strong.load(Ordering::Relaxed);
+            loop {
+                if count == 0 {
+                    return None;
+                }
+                match strong.compare_exchange_weak(count, count + 1, Ordering::Acquire, Ordering::Relaxed) {
+                    Ok(_) => return Some(Strong(weak.0)),
+                    Err(current) => count = current,
+                }
+            }
+        }
+
+        fn strong_count(this: &Strong<T>) -> usize {
+            this.inner().strong.load(Ordering::SeqCst)
+        }
+
+        fn weak_count(this: &Strong<T>) -> usize {
+            this.inner().weak.load(Ordering::SeqCst) - 1
+        }
+
+        fn weak_strong_count(weak: &Weak<T>) -> usize {
+            weak.inner().strong.load(Ordering::SeqCst)
+        }
+
+        fn weak_weak_count(weak: &Weak<T>) -> usize {
+            match weak.inner().strong.load(Ordering::SeqCst) {
+                0 => 0,
+                _ => weak.inner().weak.load(Ordering::SeqCst) - 1,
+            }
+        }
+
+        fn ptr_eq(a: &Weak<T>, b: &Weak<T>) -> bool {
+            ptr::eq(a.0.as_ptr(), b.0.as_ptr())
+        }
+
+        fn as_ptr(weak: &Weak<T>) -> *const T {
+            weak.0.as_ptr().cast()
+        }
+    }
+}
+
+struct Content {
+    /// written when dropped, so loom reports a read racing the drop
+    value: loom::cell::UnsafeCell<usize>,
+    dropped: loom::sync::Arc<AtomicBool>,
+}
+
+// SAFETY: value is only written by drop, which has exclusive access
+unsafe impl Sync for Content {}
+
+impl Drop for Content {
+    fn drop(&mut self) {
+        self.value.with_mut(|value| unsafe { *value = 0 });
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn drop_of_last_arc_races_upgrade() {
+    use loom_arc::{LoomArc, Strong};
+
+    loom::model(|| {
+        let dropped = loom::sync::Arc::new(AtomicBool::new(false));
+        let weak_self = loom::sync::Arc::new(WeakSelf::<Content, LoomArc>::default());
+
+        let owner = thread::spawn({
+            let weak_self = weak_self.clone();
+            let dropped = dropped.clone();
+            move || {
+                let content = Strong::new(Content { value: loom::cell::UnsafeCell::new(7), dropped });
+                weak_self.init(&content);
+                drop(content);
+            }
+        });
+
+        if let Some(content) = weak_self.upgrade() {
+            assert!(!content.dropped.load(Ordering::SeqCst));
+            assert_eq!(content.value.with(|value| unsafe { *value }), 7);
+        }
+
+        owner.join().unwrap();
+        assert!(weak_self.upgrade().is_none());
+        assert!(dropped.load(Ordering::SeqCst));
+    });
+}
+
+#[test]
+fn concurrent_on_drop_hooks_run_once() {
+    loom::model(|| {
+        let calls = std::sync::Arc::new(AtomicUsize::new(0));
+        let weak_self = loom::sync::Arc::new(WeakSelf::<usize>::new());
+
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let weak_self = weak_self.clone();
+                let calls = calls.clone();
+                thread::spawn(move || weak_self.on_drop(move || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                }))
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        drop(weak_self);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    });
+}
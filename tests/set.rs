@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Weak};
+
+use weak_self::set::{WeakKey, WeakSet};
+use weak_self::WeakSelf;
+
+struct Observer {
+    weak_self: WeakSelf<Observer>,
+}
+
+fn observers(count: usize) -> Vec<Arc<Observer>> {
+    (0..count).map(|_| WeakSelf::cyclic(|weak_self| Observer { weak_self })).collect()
+}
+
+#[test]
+fn insert_remove_contains() {
+    let mut set = WeakSet::new();
+    let [first, second]: [Arc<Observer>; 2] = observers(2).try_into().ok().unwrap();
+    assert!(set.insert(first.weak_self.get()));
+    assert!(!set.insert(first.weak_self.get()));
+    assert!(set.contains(&first.weak_self.get()));
+    assert!(!set.contains(&second.weak_self.get()));
+
+    assert!(set.remove(&first.weak_self.get()));
+    assert!(!set.remove(&first.weak_self.get()));
+    assert!(!set.contains(&first.weak_self.get()));
+
+    assert!(set.insert(second.weak_self.get()));
+    let weak = second.weak_self.get();
+    drop(second);
+    assert!(!set.contains(&weak));
+    assert!(!set.remove(&weak));
+    assert_eq!(set.len_live(), 0);
+}
+
+#[test]
+fn dropped_objects_are_not_inserted() {
+    let mut set = WeakSet::new();
+    let observer = observers(1).pop().unwrap();
+    let weak = observer.weak_self.get();
+    drop(observer);
+    assert!(!set.insert(weak));
+    assert!(!set.insert(Weak::new()));
+    assert_eq!(set.len_entries(), 0);
+}
+
+#[test]
+fn dead_entries_are_purged_lazily() {
+    let mut set = WeakSet::new();
+    let mut live = observers(8);
+    for observer in &live {
+        assert!(set.insert(observer.weak_self.get()));
+    }
+    // dead entries stay until the set grows
+    live.truncate(2);
+    assert_eq!(set.len_entries(), 8);
+    assert_eq!(set.len_live(), 2);
+    assert_eq!(set.upgrade_all().len(), 2);
+    assert_eq!(format!("{:?}", set).matches("WeakKey").count(), 2);
+
+    // reaching the threshold of 8 entries purges before inserting
+    live.extend(observers(1));
+    assert!(set.insert(live[2].weak_self.get()));
+    assert_eq!(set.len_entries(), 3);
+
+    // the next purge only happens at the minimum threshold again, as 2 * 2 live entries is less
+    live.extend(observers(5));
+    for observer in &live[3..] {
+        assert!(set.insert(observer.weak_self.get()));
+    }
+    assert_eq!(set.len_entries(), 8);
+
+    // with 8 live entries, the next purge happens at 16 entries
+    live.extend(observers(9));
+    for observer in &live[8..16] {
+        assert!(set.insert(observer.weak_self.get()));
+    }
+    live.drain(..12);
+    assert_eq!(set.len_entries(), 16);
+    assert!(set.insert(live[4].weak_self.get()));
+    assert_eq!(set.len_entries(), 5);
+    assert_eq!(set.len_live(), 5);
+}
+
+#[test]
+fn purge_removes_dead_entries() {
+    let mut set = WeakSet::new();
+    let mut live = observers(3);
+    for observer in &live {
+        set.insert(observer.weak_self.get());
+    }
+    live.pop();
+    set.purge();
+    assert_eq!(set.len_entries(), 2);
+}
+
+#[test]
+fn weak_key_identity() {
+    let [first, second]: [Arc<Observer>; 2] = observers(2).try_into().ok().unwrap();
+    let key = WeakKey::from(&first);
+    assert_eq!(key, WeakKey::new(first.weak_self.get()));
+    assert_ne!(key, WeakKey::from(&second));
+    assert!(Arc::ptr_eq(&key.upgrade().unwrap(), &first));
+
+    let keys: BTreeSet<WeakKey<Observer>> = [&first, &second, &first].into_iter().map(WeakKey::from).collect();
+    assert_eq!(keys.len(), 2);
+
+    drop(first);
+    assert!(!key.is_alive());
+    // the identity of a dead key stays unique, as the key keeps the allocation
+    assert_ne!(key, WeakKey::from(&observers(1)[0]));
+}
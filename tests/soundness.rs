@@ -0,0 +1,748 @@
+//! Exercises the public API of the crate, meant to run cleanly under Miri: WeakSelf and its
+//! constructors, PendingSelf, the rc flavour, the futures, and the registry, set, emitter, tree
+//! and serde helpers built on them. The tests racing other threads are worth running with several
+//! seeds, and the serde tests need the `serde` feature:
+//!
+//! MIRIFLAGS="-Zmiri-many-seeds=0..32" cargo +nightly miri test --features serde --test soundness
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use weak_self::ptr::StdRc;
+use weak_self::emitter::Emitter;
+use weak_self::registry::WeakRegistry;
+use weak_self::set::{WeakKey, WeakSet};
+use weak_self::tree::TreeNode;
+use weak_self::{HasWeakSelf, InitError, PendingSelf, WeakSelf, WeakSelfError};
+
+#[derive(Clone)]
+struct Foo {
+    weak_self: WeakSelf<Foo>,
+    value: u32,
+}
+
+impl HasWeakSelf for Foo {
+    fn weak_self(&self) -> &WeakSelf<Self> {
+        &self.weak_self
+    }
+}
+
+fn foo(value: u32) -> Arc<Foo> {
+    WeakSelf::cyclic(|weak_self| Foo { weak_self, value })
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn empty() {
+    let weak_self = WeakSelf::<u32>::new();
+    assert!(weak_self.try_get().is_none());
+    assert!(weak_self.upgrade().is_none());
+    assert_eq!(weak_self.try_arc().unwrap_err(), WeakSelfError::NotInitialized);
+    assert!(weak_self.as_ptr().is_none());
+    assert_eq!(weak_self, WeakSelf::default());
+    assert!(!weak_self.is_initialized());
+    assert!(!weak_self.is_alive());
+    assert_eq!(weak_self.strong_count(), 0);
+    assert_eq!(weak_self.weak_count(), 0);
+    assert_eq!(format!("{:?}", weak_self), r#"WeakSelf { state: "uninitialized" }"#);
+}
+
+#[test]
+#[should_panic(expected = "expected WeakSelf to be initialized")]
+fn get_uninitialized() {
+    WeakSelf::<u32>::new().get();
+}
+
+#[test]
+fn init() {
+    let content = Arc::new(Foo { weak_self: WeakSelf::new(), value: 1 });
+    content.weak_self.init(&content);
+    assert!(Arc::ptr_eq(&content, &content.weak_self.get().upgrade().unwrap()));
+    assert!(Arc::ptr_eq(&content, &content.weak_self.try_get().unwrap().upgrade().unwrap()));
+    assert!(Arc::ptr_eq(&content, &content.weak_self.upgrade().unwrap()));
+    assert!(Arc::ptr_eq(&content, &content.weak_self.arc()));
+    assert!(Arc::ptr_eq(&content, &content.shared_from_this()));
+    assert!(Arc::ptr_eq(&content, &content.try_shared_from_this().unwrap()));
+    assert!(Weak::ptr_eq(&Arc::downgrade(&content), &content.weak_from_this()));
+    assert_eq!(content.weak_self.try_init(&content), Err(InitError::AlreadyInitialized));
+    assert!(content.weak_self.is_alive());
+    assert_eq!(content.weak_self.strong_count(), 1);
+    assert_eq!(content.weak_self.weak_count(), 0);
+    assert_eq!(format!("{:?}", content.weak_self), r#"WeakSelf { state: "live", strong: 1, weak: 0 }"#);
+}
+
+#[test]
+fn init_requires_exclusive_access() {
+    let content = Arc::new(1);
+    let weak_self = WeakSelf::new();
+
+    let strong = content.clone();
+    assert_eq!(weak_self.try_init(&content), Err(InitError::StrongReferences(1)));
+    drop(strong);
+
+    let weak = Arc::downgrade(&content);
+    assert_eq!(weak_self.try_init(&content), Err(InitError::WeakReferences(1)));
+    drop(weak);
+
+    assert_eq!(weak_self.try_init(&content), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "Exclusive access")]
+fn init_shared() {
+    let content = Arc::new(1);
+    let _other = content.clone();
+    WeakSelf::new().init(&content);
+}
+
+#[test]
+fn expired() {
+    let content = foo(1);
+    let weak_self = WeakSelf::new();
+    let other = Arc::new(2);
+    weak_self.init(&other);
+    drop(other);
+    assert_eq!(weak_self.try_arc().unwrap_err(), WeakSelfError::Expired);
+    assert!(weak_self.upgrade().is_none());
+    assert!(weak_self.as_ptr().is_some());
+    assert!(weak_self.is_initialized());
+    assert!(!weak_self.is_alive());
+    assert_eq!(weak_self.strong_count(), 0);
+    assert_eq!(weak_self.weak_count(), 0);
+    assert_eq!(format!("{:?}", weak_self), r#"WeakSelf { state: "dangling" }"#);
+    drop(content);
+}
+
+#[test]
+fn cyclic_does_not_upgrade_while_building() {
+    let content = WeakSelf::cyclic(|weak_self: WeakSelf<Foo>| {
+        assert!(weak_self.upgrade().is_none());
+        Foo { weak_self, value: 3 }
+    });
+    assert_eq!(content.weak_self.arc().value, 3);
+}
+
+#[test]
+fn try_cyclic() {
+    let content = WeakSelf::try_cyclic(|weak_self| Ok::<_, ()>(Foo { weak_self, value: 4 })).unwrap();
+    assert!(Arc::ptr_eq(&content, &content.weak_self.arc()));
+    assert_eq!(content.value, 4);
+
+    let mut leaked = None;
+    let result = WeakSelf::<Foo>::try_cyclic(|weak_self| {
+        leaked = Some(weak_self.get());
+        Err("failed")
+    });
+    assert_eq!(result.err(), Some("failed"));
+    assert!(leaked.unwrap().upgrade().is_none());
+}
+
+/// true if another thread upgrades the Weak<Foo> sent by a failing build while or after it runs
+///
+/// The build should send the Weak<Foo> and wait for the other thread to receive it.
+fn upgraded_after_failed_build(build: impl FnOnce(&mpsc::Sender<Weak<Foo>>, &mpsc::Receiver<()>)) -> bool {
+    let (sender, receiver) = mpsc::channel::<Weak<Foo>>();
+    let (spinning, started) = mpsc::channel();
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let done = &done;
+        let spinner = scope.spawn(move || {
+            let weak = receiver.recv().unwrap();
+            spinning.send(()).unwrap();
+            let mut upgraded = false;
+            while !done.load(Ordering::SeqCst) {
+                upgraded |= weak.upgrade().is_some_and(|content| content.value == 7);
+                thread::yield_now();
+            }
+            upgraded
+        });
+        build(&sender, &started);
+        for _ in 0..10 {
+            thread::yield_now();
+        }
+        done.store(true, Ordering::SeqCst);
+        spinner.join().unwrap()
+    })
+}
+
+#[test]
+fn try_cyclic_failure_never_upgrades_on_other_threads() {
+    assert!(!upgraded_after_failed_build(|sender, started| {
+        let result = WeakSelf::<Foo>::try_cyclic(|weak_self| {
+            sender.send(weak_self.get()).unwrap();
+            started.recv().unwrap();
+            Err("failed")
+        });
+        assert_eq!(result.err(), Some("failed"));
+    }));
+}
+
+#[test]
+#[should_panic(expected = "builder panicked")]
+fn try_cyclic_propagates_panics() {
+    let _ = WeakSelf::<Foo>::try_cyclic(|_| -> Result<Foo, ()> { panic!("builder panicked") });
+}
+
+#[test]
+fn clone() {
+    let content = foo(6);
+    let copy = (*content).clone();
+    assert!(copy.weak_self.try_get().is_none());
+
+    let copy = WeakSelf::clone_into_arc(&*content);
+    assert!(!Arc::ptr_eq(&content, &copy));
+    assert!(Arc::ptr_eq(&copy, &copy.shared_from_this()));
+    assert_eq!(copy.value, 6);
+}
+
+#[test]
+fn pending_self() {
+    let mut early = None;
+    let content = PendingSelf::build(|pending| {
+        early = Some(pending.weak());
+        assert!(pending.weak().upgrade().is_none());
+        Foo { weak_self: WeakSelf::new(), value: 5 }
+    });
+    assert!(Arc::ptr_eq(&content, &early.unwrap().upgrade().unwrap()));
+    assert!(Arc::ptr_eq(&content, &content.shared_from_this()));
+
+    let result = PendingSelf::<Foo>::try_build(|_| Err(()));
+    assert!(result.is_err());
+}
+
+#[test]
+fn try_build_failure_never_upgrades_on_other_threads() {
+    assert!(!upgraded_after_failed_build(|sender, started| {
+        let result = PendingSelf::<Foo>::try_build(|pending| {
+            sender.send(pending.weak()).unwrap();
+            started.recv().unwrap();
+            Err("failed")
+        });
+        assert_eq!(result.err(), Some("failed"));
+    }));
+}
+
+trait Named {
+    fn name(&self) -> String;
+}
+
+struct Button {
+    weak_self: WeakSelf<dyn Named + Send + Sync>,
+}
+
+impl Named for Button {
+    fn name(&self) -> String {
+        "button".to_string()
+    }
+}
+
+#[test]
+fn unsized_content() {
+    let button = Arc::new(Button { weak_self: WeakSelf::new() });
+    button.weak_self.init_coerced(&button, |weak| weak);
+    assert_eq!(button.weak_self.arc().name(), "button");
+    let named: Arc<dyn Named + Send + Sync> = button.clone();
+    assert!(button.weak_self.is(&named));
+
+    let text: Arc<str> = Arc::from("text");
+    let weak_self = WeakSelf::<str>::new();
+    weak_self.init(&text);
+    assert_eq!(&*weak_self.arc(), "text");
+    assert!(weak_self.is(&text));
+
+    let slice: Arc<[u8]> = Arc::from(vec![1, 2, 3]);
+    let weak_self = WeakSelf::<[u8]>::new();
+    weak_self.init(&slice);
+    assert_eq!(weak_self.arc().len(), 3);
+}
+
+#[test]
+fn init_coerced_checks() {
+    let weak_self = WeakSelf::<dyn Named + Send + Sync>::new();
+    let button = Arc::new(Button { weak_self: WeakSelf::new() });
+    let other = Arc::new(Button { weak_self: WeakSelf::new() });
+
+    let shared = button.clone();
+    assert_eq!(weak_self.try_init_coerced(&button, |weak| weak), Err(InitError::StrongReferences(1)));
+    drop(shared);
+    let result = weak_self.try_init_coerced(&button, |_| Arc::downgrade(&other) as Weak<dyn Named + Send + Sync>);
+    assert_eq!(result, Err(InitError::CoercedToOtherContent));
+    assert!(weak_self.try_get().is_none());
+
+    assert_eq!(weak_self.try_init_coerced(&button, |weak| weak), Ok(()));
+    assert!(weak_self.is(&(button.clone() as Arc<dyn Named + Send + Sync>)));
+}
+
+#[test]
+#[should_panic(expected = "coercion of WeakSelf<T> must keep pointing to the same content")]
+fn init_coerced_to_other_content() {
+    let weak_self = WeakSelf::<dyn Named + Send + Sync>::new();
+    let button = Arc::new(Button { weak_self: WeakSelf::new() });
+    let other: Arc<dyn Named + Send + Sync> = Arc::new(Button { weak_self: WeakSelf::new() });
+    weak_self.init_coerced(&button, |_| Arc::downgrade(&other));
+}
+
+#[test]
+fn identity() {
+    let a = foo(1);
+    let b = foo(2);
+    assert!(a.weak_self.is(&a));
+    assert!(!a.weak_self.is(&b));
+    assert!(a.weak_self.ptr_eq(&a.weak_self));
+    assert_ne!(a.weak_self, b.weak_self);
+    assert_eq!(a.weak_self.as_ptr(), Some(Arc::as_ptr(&a)));
+    assert_eq!(hash(&a.weak_self), hash(&a.weak_self));
+    assert!(WeakSelf::new() < a.weak_self);
+    assert_eq!(a.weak_self < b.weak_self, Arc::as_ptr(&a) < Arc::as_ptr(&b));
+}
+
+#[test]
+fn drop_order() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    struct Logged {
+        weak_self: WeakSelf<Logged>,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Drop for Logged {
+        fn drop(&mut self) {
+            assert!(self.weak_self.upgrade().is_none());
+            self.log.lock().unwrap().push("content");
+        }
+    }
+
+    let content = WeakSelf::cyclic(|weak_self| Logged { weak_self, log: log.clone() });
+    let weak = content.weak_self.get();
+    for name in ["first", "second"] {
+        let log = log.clone();
+        let weak = weak.clone();
+        content.weak_self.on_drop(move || {
+            assert!(weak.upgrade().is_none());
+            log.lock().unwrap().push(name);
+        });
+    }
+
+    drop(content);
+    assert_eq!(*log.lock().unwrap(), ["content", "first", "second"]);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn on_drop_from_threads() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let content = foo(1);
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            let calls = calls.clone();
+            let content = &content;
+            scope.spawn(move || content.weak_self.on_drop(move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    drop(content);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn on_drop_panicking_hook() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let content = foo(1);
+    for name in ["first", "second", "third"] {
+        let log = log.clone();
+        content.weak_self.on_drop(move || {
+            log.lock().unwrap().push(name);
+            if name == "second" {
+                panic!("hook panicked");
+            }
+        });
+    }
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || drop(content)));
+    assert!(result.is_err());
+    assert_eq!(*log.lock().unwrap(), ["first", "second", "third"]);
+}
+
+#[test]
+fn registry_keeps_one_hook_per_object() {
+    let registry = WeakRegistry::new();
+    let content = foo(1);
+    let key: Arc<str> = Arc::from("key");
+    for _ in 0..10 {
+        assert!(registry.register(key.clone(), &content.weak_self).is_none());
+        assert!(Arc::ptr_eq(&content, &registry.remove(&key).unwrap()));
+    }
+    // neither the registry nor the drop hooks keep clones of the removed key
+    assert_eq!(Arc::strong_count(&key), 1);
+
+    registry.register(key.clone(), &content.weak_self);
+    registry.register(Arc::from("other"), &content.weak_self);
+    assert!(Arc::ptr_eq(&content, &registry.register(key.clone(), &foo(2).weak_self).unwrap()));
+    assert!(registry.get(&key).is_none());
+    assert_eq!(registry.len_live(), 1);
+
+    drop(content);
+    assert_eq!(registry.len_live(), 0);
+    assert!(registry.get("other").is_none());
+    assert_eq!(format!("{:?}", registry), "{}");
+}
+
+#[test]
+fn registry_from_threads() {
+    let registry = WeakRegistry::new();
+    let contents: Vec<_> = (0..4).map(foo).collect();
+    for content in &contents {
+        registry.register(content.value, &content.weak_self);
+    }
+    thread::scope(|scope| {
+        for content in contents {
+            let registry = &registry;
+            scope.spawn(move || {
+                // the drop hook of the content races the other threads using the registry
+                assert!(Arc::ptr_eq(&content, &registry.get(&content.value).unwrap()));
+                registry.register(content.value + 4, &content.weak_self);
+                drop(content);
+                registry.iter_live().count()
+            });
+        }
+    });
+    assert_eq!(registry.len_live(), 0);
+    assert_eq!(format!("{:?}", registry), "{}");
+}
+
+#[test]
+fn weak_set() {
+    let mut set = WeakSet::new();
+    let contents: Vec<_> = (0..3).map(foo).collect();
+    for content in &contents {
+        assert!(set.insert(content.weak_self.get()));
+    }
+    // contains and remove look entries up by &Weak<T>, cast to &WeakKey<T>
+    assert!(set.contains(&contents[0].weak_self.get()));
+    assert!(set.remove(&contents[0].weak_self.get()));
+    assert!(!set.contains(&contents[0].weak_self.get()));
+
+    let dead = contents[1].weak_self.get();
+    drop(contents);
+    assert!(!set.contains(&dead));
+    assert_eq!(set.len_live(), 0);
+    set.purge();
+    assert_eq!(set.len_entries(), 0);
+
+    let content = foo(4);
+    let key = WeakKey::from(&content);
+    assert_eq!(key, WeakKey::new(content.weak_self.get()));
+    assert_eq!(hash(&key), hash(&WeakKey::from(&content)));
+    assert!(Arc::ptr_eq(&key.upgrade().unwrap(), &content));
+    drop(content);
+    assert!(!key.is_alive());
+    assert!(key.into_weak().upgrade().is_none());
+}
+
+#[test]
+fn emitter() {
+    let emitter: Emitter<u32> = Emitter::new();
+    let total = Arc::new(AtomicUsize::new(0));
+    let contents: Vec<_> = (1..4).map(foo).collect();
+    let subscriptions: Vec<_> = contents
+        .iter()
+        .map(|content| {
+            let total = total.clone();
+            emitter.subscribe(&content.weak_self, move |this: Arc<Foo>, event: &u32| {
+                total.fetch_add((this.value * event) as usize, Ordering::SeqCst);
+            })
+        })
+        .collect();
+
+    thread::scope(|scope| {
+        let emitter = &emitter;
+        scope.spawn(move || emitter.emit(&1));
+        // dropped subscribers race the emit
+        scope.spawn(move || drop(contents));
+    });
+    let before = total.load(Ordering::SeqCst);
+    assert!(before <= 6);
+    assert_eq!(emitter.emit(&1), 0);
+    assert_eq!(total.load(Ordering::SeqCst), before);
+    drop(subscriptions);
+    assert_eq!(format!("{:?}", emitter), "Emitter { subscribers: 0 }");
+
+    // a subscriber unsubscribing itself while being called
+    let content = foo(5);
+    let subscription = Arc::new(Mutex::new(None));
+    let own = subscription.clone();
+    *subscription.lock().unwrap() = Some(emitter.subscribe(&content.weak_self, move |_, _| {
+        own.lock().unwrap().take();
+    }));
+    assert_eq!(emitter.emit(&1), 1);
+    assert_eq!(emitter.emit(&1), 0);
+}
+
+#[test]
+fn tree() {
+    let root = TreeNode::new(0);
+    let nodes: Vec<_> = (1..5).map(TreeNode::new).collect();
+    thread::scope(|scope| {
+        for (index, node) in nodes.iter().enumerate() {
+            let (root, nodes) = (&root, &nodes);
+            scope.spawn(move || {
+                root.append_child(node.clone());
+                // appending to a descendant panics, which is fine here
+                let next = &nodes[(index + 1) % nodes.len()];
+                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| node.append_child(next.clone())));
+                nodes[(index + 2) % nodes.len()].detach();
+            });
+        }
+    });
+    for node in root.depth_first() {
+        for child in node.children() {
+            assert!(Arc::ptr_eq(&child.parent().unwrap(), &node));
+        }
+    }
+    let leaf = root.breadth_first().last().unwrap();
+    assert_eq!(leaf.ancestors().last().map(|node| *node.value()), leaf.parent().map(|_| 0));
+    drop(leaf);
+
+    let weak = Arc::downgrade(&nodes[0]);
+    drop(nodes);
+    drop(root);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn callbacks() {
+    let content = foo(10);
+    let add = content.weak_self.callback(|this: Arc<Foo>, value: u32| this.value + value);
+    let mut count = 0;
+    let mut counter = content.weak_self.callback_mut(move |_: Arc<Foo>, ()| {
+        count += 1;
+        count
+    });
+    let or = content.weak_self.callback_or(0, |this: Arc<Foo>, ()| this.value);
+
+    assert_eq!(add(1), Some(11));
+    assert_eq!(counter(()), Some(1));
+    assert_eq!(counter(()), Some(2));
+    assert_eq!(or(()), 10);
+
+    drop(content);
+    assert_eq!(add(1), None);
+    assert_eq!(counter(()), None);
+    assert_eq!(or(()), 0);
+}
+
+#[test]
+fn run() {
+    let content = foo(1);
+    let mut task = pin!(content.weak_self.run(|this: Arc<Foo>, _: &mut Context| {
+        assert_eq!(Arc::strong_count(&this), 2);
+        Poll::<()>::Pending
+    }));
+    let mut cx = Context::from_waker(Waker::noop());
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(Arc::strong_count(&content), 1);
+    drop(content);
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(None));
+}
+
+#[test]
+fn run_async() {
+    /// pending once, then ready
+    async fn yield_now() {
+        let mut yielded = false;
+        std::future::poll_fn(|_| if std::mem::replace(&mut yielded, true) { Poll::Ready(()) } else { Poll::Pending }).await
+    }
+
+    let content = foo(1);
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut task = pin!(content.weak_self.run_async(|this| async move {
+        let first = this.with(|this: Arc<Foo>| this.value).await;
+        yield_now().await;
+        first + this.with(|this: Arc<Foo>| Arc::strong_count(&this) as u32).await
+    }));
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(Arc::strong_count(&content), 1);
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(Some(3)));
+
+    // the task is dropped when the content is, even in the middle of a step
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let guard = DropFlag(dropped.clone());
+    let mut task = pin!(content.weak_self.run_async(|this| async move {
+        let _guard = guard;
+        loop {
+            yield_now().await;
+            this.with(|_: Arc<Foo>| ()).await;
+        }
+    }));
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Pending);
+    drop(content);
+    assert!(!dropped.load(Ordering::SeqCst));
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(None));
+    assert!(dropped.load(Ordering::SeqCst));
+    assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(None));
+}
+
+#[test]
+fn wait() {
+    let content = Arc::new(Foo { weak_self: WeakSelf::new(), value: 1 });
+    assert!(content.weak_self.wait_timeout(Duration::from_millis(1)).is_none());
+    thread::scope(|scope| {
+        let waiter = scope.spawn(|| content.weak_self.wait().upgrade().map(|this| this.value));
+        content.weak_self.init(&content);
+        assert_eq!(waiter.join().unwrap(), Some(1));
+    });
+    assert!(content.weak_self.wait_timeout(Duration::from_millis(1)).is_some());
+}
+
+#[test]
+fn initialized() {
+    let content = Arc::new(Foo { weak_self: WeakSelf::new(), value: 1 });
+    let mut cx = Context::from_waker(Waker::noop());
+    {
+        let mut future = pin!(content.weak_self.initialized());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        content.weak_self.init(&content);
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+    }
+    let mut future = pin!(content.weak_self.initialized());
+    assert!(future.as_mut().poll(&mut cx).is_ready());
+}
+
+#[test]
+fn rc_flavours() {
+    struct Node {
+        weak_self: WeakSelf<Node, StdRc>,
+        local: weak_self::rc::WeakSelf<Node>,
+    }
+
+    let node = Rc::new(Node { weak_self: WeakSelf::default(), local: Default::default() });
+    node.weak_self.init(&node);
+    node.local.init(&Rc::new(Node { weak_self: WeakSelf::default(), local: Default::default() }));
+    assert!(Rc::ptr_eq(&node, &node.weak_self.arc()));
+    assert!(node.weak_self.is(&node));
+    assert!(node.local.get().upgrade().is_none());
+    assert_eq!(format!("{:?}", node.local), r#"WeakSelf { state: "dangling" }"#);
+
+    struct Local {
+        weak_self: weak_self::rc::WeakSelf<Local>,
+    }
+
+    let local = weak_self::rc::WeakSelf::cyclic(|weak_self| Local { weak_self });
+    assert!(Rc::ptr_eq(&local, &local.weak_self.arc()));
+    assert!(local.weak_self.is(&local));
+    assert_eq!(local.weak_self.strong_count(), 1);
+    assert_eq!(local.weak_self.try_init(&local), Err(InitError::AlreadyInitialized));
+    assert_eq!(local.weak_self.clone(), weak_self::rc::WeakSelf::new());
+
+    let local = Rc::new(Local { weak_self: weak_self::rc::WeakSelf::new() });
+    let shared = local.clone();
+    assert_eq!(local.weak_self.try_init(&local), Err(InitError::StrongReferences(1)));
+    drop(shared);
+    assert_eq!(local.weak_self.try_init(&local), Ok(()));
+    assert!(Rc::ptr_eq(&local, &local.weak_self.arc()));
+
+    // the local hook list runs hooks in registration order, also after a panicking hook
+    let log = Arc::new(Mutex::new(Vec::new()));
+    for name in ["first", "panics", "last"] {
+        let log = log.clone();
+        local.weak_self.on_drop(move || {
+            log.lock().unwrap().push(name);
+            assert_ne!(name, "panics");
+        });
+    }
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || drop(local)));
+    assert!(result.is_err());
+    assert_eq!(*log.lock().unwrap(), ["first", "panics", "last"]);
+
+    // hooks of single-threaded kinds need not be Send
+    let dropped = Rc::new(Cell::new(false));
+    let local = weak_self::rc::WeakSelf::cyclic(|weak_self| Local { weak_self });
+    let flag = dropped.clone();
+    local.weak_self.on_drop(move || flag.set(true));
+    let node = Rc::new(Node { weak_self: WeakSelf::default(), local: Default::default() });
+    let flag = dropped.clone();
+    node.weak_self.on_drop(move || assert!(flag.get()));
+    drop(local);
+    assert!(dropped.get());
+    drop(node);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Named {
+        weak_self: WeakSelf<Named>,
+        name: String,
+    }
+
+    impl HasWeakSelf for Named {
+        fn weak_self(&self) -> &WeakSelf<Self> {
+            &self.weak_self
+        }
+    }
+
+    let named = WeakSelf::cyclic(|weak_self| Named { weak_self, name: "named".to_string() });
+    let json = serde_json::to_string(&*named).unwrap();
+    assert_eq!(json, r#"{"weak_self":null,"name":"named"}"#);
+
+    let copy: Named = serde_json::from_str(&json).unwrap();
+    assert!(copy.weak_self.try_get().is_none());
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let copy: Arc<Named> = weak_self::serde::deserialize_arc(&mut deserializer).unwrap();
+    assert!(Arc::ptr_eq(&copy, &copy.shared_from_this()));
+    assert_eq!(copy.name, "named");
+
+    let copy: Named = serde_json::from_str(r#"{"name":"missing"}"#).unwrap();
+    assert!(copy.weak_self.try_get().is_none());
+    assert!(serde_json::from_str::<WeakSelf<Named>>("1").is_err());
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Local {
+        weak_self: weak_self::rc::WeakSelf<Local>,
+        name: String,
+    }
+
+    impl weak_self::rc::HasWeakSelf for Local {
+        fn weak_self(&self) -> &weak_self::rc::WeakSelf<Self> {
+            &self.weak_self
+        }
+    }
+
+    let local = weak_self::rc::WeakSelf::cyclic(|weak_self| Local { weak_self, name: "local".to_string() });
+    let json = serde_json::to_string(&*local).unwrap();
+    assert_eq!(json, r#"{"weak_self":null,"name":"local"}"#);
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let copy: Rc<Local> = weak_self::serde::deserialize_rc(&mut deserializer).unwrap();
+    assert!(Rc::ptr_eq(&copy, &weak_self::rc::HasWeakSelf::shared_from_this(&*copy)));
+    assert_eq!(copy.name, "local");
+
+    let copy: Local = serde_json::from_str(r#"{"name":"missing"}"#).unwrap();
+    assert!(copy.weak_self.try_get().is_none());
+    assert!(serde_json::from_str::<weak_self::rc::WeakSelf<Local>>("1").is_err());
+}
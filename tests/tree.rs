@@ -0,0 +1,152 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use weak_self::tree::TreeNode;
+
+/// value counting how many nodes have been dropped
+struct Counted(Arc<AtomicUsize>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// check that the children of each node point back to it
+fn assert_linked<T>(root: &Arc<TreeNode<T>>) {
+    for node in root.depth_first() {
+        for child in node.children() {
+            assert!(Arc::ptr_eq(&child.parent().unwrap(), &node));
+        }
+    }
+}
+
+#[test]
+fn dropping_the_root_frees_every_node() {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let node = || TreeNode::new(Counted(dropped.clone()));
+
+    let root = node();
+    let mut nodes = vec![root.clone()];
+    for index in 1..20 {
+        let child = node();
+        nodes[(index - 1) / 3].append_child(child.clone());
+        nodes.push(child);
+    }
+    // reparent a few subtrees
+    nodes[10].append_child(nodes[2].clone());
+    nodes[19].append_child(nodes[5].clone());
+    nodes[1].detach();
+    root.append_child(nodes[1].clone());
+    assert_linked(&root);
+    assert_eq!(root.depth_first().count(), 20);
+
+    drop(nodes);
+    assert_eq!(dropped.load(Ordering::SeqCst), 0);
+    drop(root);
+    assert_eq!(dropped.load(Ordering::SeqCst), 20);
+}
+
+#[test]
+fn dropping_a_deep_tree_does_not_overflow() {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    // grow the tree at the root, as appending to a leaf walks all its ancestors
+    let mut root = TreeNode::new(Counted(dropped.clone()));
+    for _ in 0..100_000 {
+        let parent = TreeNode::new(Counted(dropped.clone()));
+        parent.append_child(root);
+        root = parent;
+    }
+    drop(root);
+    assert_eq!(dropped.load(Ordering::SeqCst), 100_001);
+}
+
+#[test]
+#[should_panic(expected = "cannot append a TreeNode to itself or to one of its descendants")]
+fn append_ancestor_panics() {
+    let root = TreeNode::new(0);
+    let child = TreeNode::new(1);
+    root.append_child(child.clone());
+    child.append_child(root);
+}
+
+#[test]
+fn concurrent_opposite_appends_do_not_create_cycles() {
+    for _ in 0..100 {
+        let a = TreeNode::new("a");
+        let b = TreeNode::new("b");
+        let barrier = Barrier::new(2);
+        let (first, second) = thread::scope(|scope| {
+            let first = scope.spawn(|| {
+                barrier.wait();
+                catch_unwind(AssertUnwindSafe(|| a.append_child(b.clone()))).is_ok()
+            });
+            let second = scope.spawn(|| {
+                barrier.wait();
+                catch_unwind(AssertUnwindSafe(|| b.append_child(a.clone()))).is_ok()
+            });
+            (first.join().unwrap(), second.join().unwrap())
+        });
+        assert!(first != second);
+        let (parent, child) = if first { (&a, &b) } else { (&b, &a) };
+        assert!(parent.parent().is_none());
+        assert!(Arc::ptr_eq(&child.parent().unwrap(), parent));
+        child.detach();
+    }
+}
+
+#[test]
+fn concurrent_reparenting_keeps_back_links() {
+    let roots = [TreeNode::new(0), TreeNode::new(1)];
+    let nodes: Vec<_> = (0..8).map(TreeNode::new).collect();
+    thread::scope(|scope| {
+        for offset in 0..4 {
+            let (roots, nodes) = (&roots, &nodes);
+            scope.spawn(move || {
+                for round in 0..200 {
+                    let node = &nodes[(round * 3 + offset) % nodes.len()];
+                    match round % 3 {
+                        0 => roots[offset % 2].append_child(node.clone()),
+                        1 => node.detach(),
+                        _ => {
+                            let parent = &nodes[(round + offset) % nodes.len()];
+                            // appending to a descendant panics, which is fine here
+                            let _ = catch_unwind(AssertUnwindSafe(|| parent.append_child(node.clone())));
+                        }
+                    }
+                }
+            });
+        }
+    });
+    for root in &roots {
+        assert_linked(root);
+    }
+    for node in &nodes {
+        let parent = node.parent();
+        if let Some(parent) = parent {
+            assert!(parent.children().iter().any(|child| Arc::ptr_eq(child, node)));
+        }
+    }
+}
+
+#[test]
+fn drop_of_a_value_may_change_other_trees() {
+    struct Reparent(Arc<TreeNode<u32>>, Arc<TreeNode<u32>>);
+
+    impl Drop for Reparent {
+        fn drop(&mut self) {
+            self.0.append_child(self.1.clone());
+        }
+    }
+
+    let target = TreeNode::new(0);
+    let moved = TreeNode::new(1);
+    TreeNode::new(7).append_child(moved.clone());
+    let root = TreeNode::new(Reparent(target.clone(), moved.clone()));
+    root.append_child(TreeNode::new(Reparent(target.clone(), TreeNode::new(2))));
+    drop(root);
+    assert_eq!(target.children().len(), 2);
+    assert!(Arc::ptr_eq(&moved.parent().unwrap(), &target));
+}
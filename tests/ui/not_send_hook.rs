@@ -0,0 +1,10 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use weak_self::WeakSelf;
+
+fn main() {
+    // hooks of an Arc based WeakSelf may run on any thread
+    let weak_self = WeakSelf::<u8>::new();
+    let flag = Rc::new(Cell::new(false));
+    weak_self.on_drop(move || flag.set(true));
+}
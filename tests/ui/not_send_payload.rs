@@ -0,0 +1,8 @@
+use std::rc::Rc;
+use std::thread;
+use weak_self::WeakSelf;
+
+fn main() {
+    let weak_self = WeakSelf::<Rc<u8>>::new();
+    thread::spawn(move || drop(weak_self));
+}
@@ -0,0 +1,9 @@
+use std::cell::Cell;
+use std::sync::Arc;
+use std::thread;
+use weak_self::WeakSelf;
+
+fn main() {
+    let weak_self = Arc::new(WeakSelf::<Cell<u8>>::new());
+    thread::spawn(move || weak_self.try_get().is_some());
+}
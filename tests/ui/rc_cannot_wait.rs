@@ -0,0 +1,8 @@
+use weak_self::ptr::StdRc;
+use weak_self::WeakSelf;
+
+fn main() {
+    // only the initializing thread could wake the waiter
+    let weak_self = WeakSelf::<u8, StdRc>::default();
+    weak_self.wait();
+}
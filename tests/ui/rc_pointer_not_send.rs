@@ -0,0 +1,8 @@
+use std::thread;
+use weak_self::ptr::StdRc;
+use weak_self::WeakSelf;
+
+fn main() {
+    let weak_self = WeakSelf::<u8, StdRc>::default();
+    thread::spawn(move || drop(weak_self));
+}
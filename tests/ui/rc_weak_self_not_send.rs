@@ -0,0 +1,6 @@
+use std::thread;
+
+fn main() {
+    let weak_self = weak_self::rc::WeakSelf::<u8>::new();
+    thread::spawn(move || drop(weak_self));
+}
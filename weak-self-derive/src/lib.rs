@@ -0,0 +1,136 @@
+//! Derive macro for [WeakSelf](https://docs.rs/weak-self).
+//!
+//! This crate is re-exported by `weak-self` behind its `derive` feature, use it from there.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Field, Fields, Member, Meta, Type};
+
+///Derives `HasWeakSelf` and an `into_arc` constructor for a struct holding a `WeakSelf<Self>` field.
+///
+///The field is either the only field whose type is named `WeakSelf`, or the one marked with
+///`#[weak_self]`. The generated `into_arc(self) -> Arc<Self>` moves the value into a new Arc
+///built with `PendingSelf::build`, so the field is linked before anybody can see the Arc. The field
+///itself is kept, so hooks registered with `on_drop` before `into_arc` still run once the Arc is
+///dropped. `into_arc` panics if the field is already linked to another Arc.
+///
+///```rust
+/// use weak_self::{HasWeakSelf, WeakSelf};
+/// use std::sync::Arc;
+///
+/// #[derive(WeakSelf)]
+/// pub struct Foo<T> {
+///     weak_self: WeakSelf<Foo<T>>,
+///     value: T,
+/// }
+///
+/// let foo = Foo { weak_self: WeakSelf::new(), value: 42 }.into_arc();
+/// assert!(Arc::ptr_eq(&foo, &foo.shared_from_this()));
+/// assert_eq!(foo.value, 42);
+///```
+///
+///A struct without any matching field is rejected:
+///
+///```compile_fail
+/// #[derive(weak_self::WeakSelf)]
+/// pub struct Foo {
+///     value: u32,
+/// }
+///```
+#[proc_macro_derive(WeakSelf, attributes(weak_self))]
+pub fn derive_weak_self(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => return Err(Error::new(input.ident.span(), "WeakSelf can only be derived for structs")),
+    };
+    let member = find_field(fields, input.ident.span())?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::weak_self::HasWeakSelf for #name #ty_generics #where_clause {
+            fn weak_self(&self) -> &::weak_self::WeakSelf<Self> {
+                &self.#member
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Moves this value into a new Arc and initializes its WeakSelf field, keeping the
+            /// drop hooks registered on it
+            pub fn into_arc(self) -> ::weak_self::__private::Arc<Self> {
+                ::weak_self::PendingSelf::build(move |_| self)
+            }
+        }
+    })
+}
+
+/// find the field marked with #[weak_self], or else the only field of type WeakSelf
+fn find_field(fields: &Fields, span: Span) -> Result<Member, Error> {
+    let fields: Vec<(Member, &Field)> = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| (member(index, field), field))
+        .collect();
+
+    let mut marked = Vec::new();
+    for (candidate, field) in &fields {
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("weak_self")) {
+            if !matches!(attr.meta, Meta::Path(_)) {
+                return Err(Error::new_spanned(attr, "#[weak_self] does not take arguments"));
+            }
+            marked.push((candidate, attr));
+        }
+    }
+    if let Some((candidate, _)) = marked.first() {
+        if let Some((_, attr)) = marked.get(1) {
+            return Err(Error::new_spanned(attr, "only one field can be marked with #[weak_self]"));
+        }
+        return Ok((*candidate).clone());
+    }
+
+    let candidates: Vec<&(Member, &Field)> =
+        fields.iter().filter(|(_, field)| is_weak_self(&field.ty)).collect();
+    match candidates.as_slice() {
+        [(candidate, _)] => Ok(candidate.clone()),
+        [] => Err(Error::new(
+            span,
+            "no WeakSelf field found, add a field of type WeakSelf<Self> or mark one with #[weak_self]",
+        )),
+        [_, (_, second), ..] => Err(Error::new_spanned(
+            &second.ty,
+            "more than one WeakSelf field found, mark the one to use with #[weak_self]",
+        )),
+    }
+}
+
+fn member(index: usize, field: &Field) -> Member {
+    match field.ident {
+        Some(ref ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(index.into()),
+    }
+}
+
+/// true for types named WeakSelf, except for the single-threaded rc::WeakSelf
+fn is_weak_self(ty: &Type) -> bool {
+    let path = match ty {
+        Type::Path(path) if path.qself.is_none() => &path.path,
+        _ => return false,
+    };
+    let mut segments = path.segments.iter().rev();
+    match segments.next() {
+        Some(last) if last.ident == "WeakSelf" => {
+            !matches!(segments.next(), Some(parent) if parent.ident == "rc")
+        }
+        _ => false,
+    }
+}
+
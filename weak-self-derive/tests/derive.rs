@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use weak_self::{HasWeakSelf, WeakSelf};
+
+#[derive(WeakSelf)]
+struct Plain {
+    weak_self: WeakSelf<Plain>,
+    value: u32,
+}
+
+#[test]
+fn into_arc_keeps_drop_hooks() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let plain = Plain { weak_self: WeakSelf::new(), value: 1 };
+    let counter = calls.clone();
+    plain.weak_self.on_drop(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let plain = plain.into_arc();
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    assert!(Arc::ptr_eq(&plain, &plain.shared_from_this()));
+    assert_eq!(plain.value, 1);
+
+    drop(plain);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
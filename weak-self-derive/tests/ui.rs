@@ -0,0 +1,9 @@
+//! Checks which structs the derive accepts, and the spans of its errors.
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass_*.rs");
+    cases.compile_fail("tests/ui/fail_*.rs");
+}
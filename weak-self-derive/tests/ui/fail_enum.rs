@@ -0,0 +1,8 @@
+use weak_self::WeakSelf;
+
+#[derive(WeakSelf)]
+enum Enum {
+    Variant(WeakSelf<Enum>),
+}
+
+fn main() {}
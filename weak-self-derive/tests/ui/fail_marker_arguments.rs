@@ -0,0 +1,15 @@
+use weak_self::WeakSelf;
+
+#[derive(WeakSelf)]
+struct Arguments {
+    #[weak_self(field)]
+    weak_self: WeakSelf<Arguments>,
+}
+
+#[derive(WeakSelf)]
+struct Value {
+    #[weak_self = "field"]
+    weak_self: WeakSelf<Value>,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[derive(weak_self::WeakSelf)]
+struct NoField {
+    value: u32,
+}
+
+fn main() {}
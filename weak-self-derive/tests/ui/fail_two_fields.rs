@@ -0,0 +1,10 @@
+use weak_self::WeakSelf;
+
+#[derive(WeakSelf)]
+struct TwoFields {
+    first: WeakSelf<TwoFields>,
+    value: u32,
+    second: WeakSelf<TwoFields>,
+}
+
+fn main() {}
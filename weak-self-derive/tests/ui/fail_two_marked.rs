@@ -0,0 +1,11 @@
+use weak_self::WeakSelf;
+
+#[derive(WeakSelf)]
+struct TwoMarked {
+    #[weak_self]
+    first: WeakSelf<TwoMarked>,
+    #[weak_self]
+    second: WeakSelf<TwoMarked>,
+}
+
+fn main() {}
@@ -0,0 +1,29 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use weak_self::{HasWeakSelf, WeakSelf};
+
+#[derive(WeakSelf)]
+struct Generic<'a, T: Debug, const N: usize> {
+    weak_self: WeakSelf<Generic<'a, T, N>>,
+    values: [T; N],
+    name: &'a str,
+}
+
+#[derive(WeakSelf)]
+struct Bounded<T>
+    where T: Clone + Default
+{
+    value: T,
+    weak_self: WeakSelf<Self>,
+}
+
+fn main() {
+    let generic = Generic { weak_self: WeakSelf::new(), values: [1, 2], name: "generic" }.into_arc();
+    assert!(Arc::ptr_eq(&generic, &generic.shared_from_this()));
+    assert_eq!((generic.values, generic.name), ([1, 2], "generic"));
+
+    let bounded = Bounded { value: String::from("bounded"), weak_self: WeakSelf::new() }.into_arc();
+    assert!(Arc::ptr_eq(&bounded, &bounded.shared_from_this()));
+    assert_eq!(bounded.value, "bounded");
+}
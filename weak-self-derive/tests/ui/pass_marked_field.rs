@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use weak_self::{HasWeakSelf, WeakSelf};
+
+/// the marked field is used although there are two WeakSelf fields
+#[derive(WeakSelf)]
+struct Marked {
+    other: WeakSelf<Marked>,
+    #[weak_self]
+    weak_self: WeakSelf<Marked>,
+}
+
+/// a field with a renamed type is found through the marker only
+type Renamed<T> = WeakSelf<T>;
+
+#[derive(WeakSelf)]
+struct Aliased(#[weak_self] Renamed<Aliased>);
+
+/// rc::WeakSelf is not a candidate
+#[derive(WeakSelf)]
+struct WithRc {
+    weak_self: WeakSelf<WithRc>,
+    #[allow(dead_code)]
+    local: weak_self::rc::WeakSelf<u32>,
+}
+
+fn main() {
+    let marked = Marked { other: WeakSelf::new(), weak_self: WeakSelf::new() }.into_arc();
+    assert!(Arc::ptr_eq(&marked, &marked.shared_from_this()));
+    assert!(std::ptr::eq(marked.weak_self(), &marked.weak_self));
+    assert!(marked.other.try_get().is_none());
+
+    let aliased = Aliased(WeakSelf::new()).into_arc();
+    assert!(Arc::ptr_eq(&aliased, &aliased.shared_from_this()));
+
+    let with_rc = WithRc { weak_self: WeakSelf::new(), local: weak_self::rc::WeakSelf::new() }.into_arc();
+    assert!(Arc::ptr_eq(&with_rc, &with_rc.shared_from_this()));
+}
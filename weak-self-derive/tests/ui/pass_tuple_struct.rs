@@ -0,0 +1,13 @@
+use std::sync::Arc;
+
+use weak_self::{HasWeakSelf, WeakSelf};
+
+#[derive(WeakSelf)]
+struct Tuple(u32, WeakSelf<Tuple>);
+
+fn main() {
+    let tuple = Tuple(7, WeakSelf::new()).into_arc();
+    assert!(Arc::ptr_eq(&tuple, &tuple.shared_from_this()));
+    assert!(std::ptr::eq(tuple.weak_self(), &tuple.1));
+    assert_eq!(tuple.0, 7);
+}